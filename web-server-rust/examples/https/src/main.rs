@@ -0,0 +1,15 @@
+use std::io;
+
+use web_server::App;
+
+fn main() -> io::Result<()> {
+    let app = App::new();
+
+    app.get("/", |_, res| res.send("Secure Home Page\n"));
+
+    app.listen_tls("127.0.0.1:8443", "cert.pem", "key.pem", |addr| {
+        println!("Server Running in Addr: https://{}", addr);
+    })?;
+
+    Ok(())
+}
@@ -1,37 +1,64 @@
 #![allow(clippy::type_complexity)]
 
+mod router;
+
 use std::{
-    collections::HashMap,
-    io::{self, BufReader, BufWriter},
-    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    io,
+    net::{SocketAddr, TcpListener, ToSocketAddrs},
+    path::PathBuf,
     sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+#[cfg(unix)]
+use crate::server::UnixSocket;
+#[cfg(feature = "tls")]
+use crate::server::{TlsConfig, TlsListener};
+use crate::{
+    error::HttpError,
+    method::Method,
+    request::HttpRequest,
+    response::HttpResponse,
+    serve_dir::ServeDir,
+    server::{
+        HttpServer, Listener, QueuePolicy, Reader, ServerConfig, ServerHandle, ServerMetrics,
+        ServerState, Writer,
+    },
 };
 
-use crate::{request::HttpRequest, response::HttpResponse, server::HttpServer};
+pub use router::Params;
+pub(crate) use router::Route;
+use router::Router;
+
+pub(crate) type Handler<R, W> =
+    Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>;
 
 macro_rules! insert_handler {
-    ($name:ident) => {
+    ($name:ident, $method:expr) => {
         #[inline]
-        pub fn $name<F>(&self, path: &'static str, f: F)
+        pub fn $name<F>(&self, path: &str, f: F)
         where
-            F: Fn(
-                    HttpRequest<BufReader<TcpStream>>,
-                    HttpResponse<BufWriter<TcpStream>>,
-                ) -> io::Result<()>
+            F: Fn(HttpRequest<Reader>, HttpResponse<Writer>) -> io::Result<()>
                 + Send
                 + Sync
                 + 'static,
         {
-            self.$name.write().unwrap().insert(path, Box::new(f));
+            self.router
+                .write()
+                .unwrap()
+                .insert($method, path, Box::new(f));
         }
     };
 }
 
-pub struct App<R, W>
+pub struct App<R = Reader, W = Writer>
 where
     R: io::Read,
     W: io::Write,
 {
+    pub(crate) config: RwLock<ServerConfig>,
+    pub(crate) metrics: ServerMetrics,
     pub(crate) middleware: RwLock<
         Vec<
             Box<
@@ -45,126 +72,210 @@ where
             >,
         >,
     >,
-    pub(crate) connect: RwLock<
-        HashMap<
-            &'static str,
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
-    >,
-    pub(crate) get: RwLock<
-        HashMap<
-            &'static str,
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
-    >,
-    pub(crate) post: RwLock<
-        HashMap<
-            &'static str,
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
-    >,
-    pub(crate) delete: RwLock<
-        HashMap<
-            &'static str,
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
-    >,
-    pub(crate) head: RwLock<
-        HashMap<
-            &'static str,
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
-    >,
-    pub(crate) put: RwLock<
-        HashMap<
-            &'static str,
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
-    >,
-    pub(crate) patch: RwLock<
-        HashMap<
-            &'static str,
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
-    >,
-    pub(crate) trace: RwLock<
-        HashMap<
-            &'static str,
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
-    >,
-    pub(crate) options: RwLock<
-        HashMap<
-            &'static str,
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
-    >,
-    pub(crate) unknown: RwLock<
-        Option<
-            Box<dyn Fn(HttpRequest<R>, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>,
-        >,
+    pub(crate) router: RwLock<Router<Handler<R, W>>>,
+    pub(crate) unknown: RwLock<Option<Handler<R, W>>>,
+    pub(crate) error_handler: RwLock<
+        Option<Box<dyn Fn(&HttpError, HttpResponse<W>) -> io::Result<()> + Send + Sync + 'static>>,
     >,
 }
 
-impl Default for App<BufReader<TcpStream>, BufWriter<TcpStream>> {
+impl Default for App<Reader, Writer> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl App<BufReader<TcpStream>, BufWriter<TcpStream>> {
+impl App<Reader, Writer> {
     pub fn new() -> Self {
         Self {
+            config: RwLock::new(ServerConfig::default()),
+            metrics: ServerMetrics::default(),
             middleware: RwLock::new(Vec::new()),
-            connect: RwLock::new(HashMap::new()),
-            get: RwLock::new(HashMap::new()),
-            post: RwLock::new(HashMap::new()),
-            delete: RwLock::new(HashMap::new()),
-            head: RwLock::new(HashMap::new()),
-            put: RwLock::new(HashMap::new()),
-            patch: RwLock::new(HashMap::new()),
-            trace: RwLock::new(HashMap::new()),
-            options: RwLock::new(HashMap::new()),
+            router: RwLock::new(Router::new()),
             unknown: RwLock::new(None),
+            error_handler: RwLock::new(None),
         }
     }
+    pub fn body_limit(&self, limit: usize) {
+        self.config.write().unwrap().body_limit = limit;
+    }
+    // Largest body `req.form()` accepts, in bytes.
+    pub fn form_limit(&self, limit: usize) {
+        self.config.write().unwrap().limits.form_size = limit;
+    }
+    // Also caps the number of parts in a multipart body.
+    pub fn form_fields(&self, max: usize) {
+        self.config.write().unwrap().limits.form_fields = max;
+    }
+    // Uploads tend to be larger than other bodies, multipart bodies are
+    // limited by this instead of `body_limit`.
+    pub fn multipart_limit(&self, limit: usize) {
+        self.config.write().unwrap().limits.multipart_size = limit;
+    }
+    pub fn part_limit(&self, limit: usize) {
+        self.config.write().unwrap().limits.part_size = limit;
+    }
+    // Spooled parts larger than this are written to a temporary file.
+    pub fn part_memory(&self, limit: usize) {
+        self.config.write().unwrap().limits.part_memory = limit;
+    }
+    // Directory for those files instead of the system's temporary one.
+    // On the same file system as their destination, `persist` is a rename.
+    pub fn upload_dir<P: Into<PathBuf>>(&self, dir: P) {
+        self.config.write().unwrap().limits.upload_dir = Some(dir.into().into());
+    }
+    // Weak `ETag`s for bodies sent with `send`, `send_bytes` and `json`, on
+    // by default. Files always get a strong one.
+    pub fn etag(&self, enabled: bool) {
+        self.config.write().unwrap().etag = enabled;
+    }
+    pub fn keep_alive_timeout(&self, timeout: Duration) {
+        self.config.write().unwrap().keep_alive_timeout = timeout;
+    }
+    pub fn max_requests(&self, max: usize) {
+        self.config.write().unwrap().max_requests = max.max(1);
+    }
+    pub fn workers(&self, workers: usize) {
+        self.config.write().unwrap().workers = workers.max(1);
+    }
+    pub fn queue_size(&self, size: usize) {
+        self.config.write().unwrap().queue_size = size;
+    }
+    pub fn queue_policy(&self, policy: QueuePolicy) {
+        self.config.write().unwrap().queue_policy = policy;
+    }
+    pub fn shutdown_timeout(&self, timeout: Duration) {
+        self.config.write().unwrap().shutdown_timeout = timeout;
+    }
+    // Ex: app.unix_socket_mode(0o660);
+    #[cfg(unix)]
+    pub fn unix_socket_mode(&self, mode: u32) {
+        self.config.write().unwrap().socket_mode = Some(mode);
+    }
+    #[inline]
+    pub fn metrics(&self) -> ServerMetrics {
+        self.metrics.clone()
+    }
+    pub fn fallback<F>(&self, f: F)
+    where
+        F: Fn(HttpRequest<Reader>, HttpResponse<Writer>) -> io::Result<()> + Send + Sync + 'static,
+    {
+        *self.unknown.write().unwrap() = Some(Box::new(f));
+    }
+    // Renders the response for requests that could not be parsed or whose
+    // handler failed before sending anything. The status is already set.
+    pub fn on_error<F>(&self, f: F)
+    where
+        F: Fn(&HttpError, HttpResponse<Writer>) -> io::Result<()> + Send + Sync + 'static,
+    {
+        *self.error_handler.write().unwrap() = Some(Box::new(f));
+    }
     pub fn use_middleware<F>(&self, f: F)
     where
         F: Fn(
-                HttpRequest<BufReader<TcpStream>>,
-                HttpResponse<BufWriter<TcpStream>>,
-            ) -> io::Result<
-                Option<(
-                    HttpRequest<BufReader<TcpStream>>,
-                    HttpResponse<BufWriter<TcpStream>>,
-                )>,
-            > + Send
+                HttpRequest<Reader>,
+                HttpResponse<Writer>,
+            ) -> io::Result<Option<(HttpRequest<Reader>, HttpResponse<Writer>)>>
+            + Send
             + Sync
             + 'static,
     {
         self.middleware.write().unwrap().push(Box::new(f));
     }
-    insert_handler!(connect);
-    insert_handler!(get);
-    insert_handler!(post);
-    insert_handler!(delete);
-    insert_handler!(head);
-    insert_handler!(put);
-    insert_handler!(patch);
-    insert_handler!(trace);
-    insert_handler!(options);
+    insert_handler!(connect, Method::Connect);
+    insert_handler!(get, Method::Get);
+    insert_handler!(post, Method::Post);
+    insert_handler!(delete, Method::Delete);
+    insert_handler!(head, Method::Head);
+    insert_handler!(put, Method::Put);
+    insert_handler!(patch, Method::Patch);
+    insert_handler!(trace, Method::Trace);
+    insert_handler!(options, Method::Options);
+    // Serves the files below `dir` for `GET` and `HEAD` requests under
+    // `prefix`, `index.html` for directories.
+    // Ex: app.serve_dir("/static", "./public");
+    #[inline]
+    pub fn serve_dir<P: Into<PathBuf>>(&self, prefix: &str, dir: P) {
+        self.serve_dir_with(prefix, ServeDir::new(dir));
+    }
+    pub fn serve_dir_with(&self, prefix: &str, dir: ServeDir) {
+        let prefix = prefix.trim_end_matches('/');
+        let dir = Arc::new(dir);
+        let root = dir.clone();
+        self.get(
+            if prefix.is_empty() { "/" } else { prefix },
+            move |req, res| root.serve(req, res),
+        );
+        self.get(&format!("{}/*path", prefix), move |req, res| {
+            dir.serve(req, res)
+        });
+    }
     pub fn listen<A: ToSocketAddrs, F: Fn(SocketAddr)>(
         self,
         addr: A,
         callback: F,
     ) -> io::Result<()> {
         let listener = TcpListener::bind(addr)?;
-        callback(listener.local_addr().unwrap());
+        let addr = listener.local_addr()?;
+        let handle = self.spawn_on(listener)?;
+        callback(addr);
+        handle.wait()
+    }
+    #[inline]
+    pub fn spawn<A: ToSocketAddrs>(self, addr: A) -> io::Result<ServerHandle> {
+        self.spawn_on(TcpListener::bind(addr)?)
+    }
+    #[cfg(feature = "tls")]
+    pub fn listen_tls<A, P, K, F>(self, addr: A, cert: P, key: K, callback: F) -> io::Result<()>
+    where
+        A: ToSocketAddrs,
+        P: AsRef<std::path::Path>,
+        K: AsRef<std::path::Path>,
+        F: Fn(SocketAddr),
+    {
+        let handle = self.spawn_tls(addr, TlsConfig::new(cert, key)?)?;
+        callback(handle.local_addr());
+        handle.wait()
+    }
+    #[cfg(feature = "tls")]
+    pub fn spawn_tls<A: ToSocketAddrs>(self, addr: A, tls: TlsConfig) -> io::Result<ServerHandle> {
+        self.spawn_on(TlsListener {
+            inner: TcpListener::bind(addr)?,
+            config: tls.build(),
+        })
+    }
+    #[cfg(unix)]
+    pub fn listen_unix<P: AsRef<std::path::Path>, F: Fn(&std::path::Path)>(
+        self,
+        path: P,
+        callback: F,
+    ) -> io::Result<()> {
+        let handle = self.spawn_unix(&path)?;
+        callback(path.as_ref());
+        handle.wait()
+    }
+    #[cfg(unix)]
+    pub fn spawn_unix<P: AsRef<std::path::Path>>(self, path: P) -> io::Result<ServerHandle> {
+        let mode = self.config.read().unwrap().socket_mode;
+        self.spawn_on(UnixSocket::bind(path.as_ref(), mode)?)
+    }
+    // Serves connections from any transport until shut down.
+    pub fn listen_on<L: Listener + 'static>(self, listener: L) -> io::Result<()> {
+        self.spawn_on(listener)?.wait()
+    }
+    pub fn spawn_on<L: Listener + 'static>(self, listener: L) -> io::Result<ServerHandle> {
+        let addr = listener.local_addr();
+        let state = Arc::new(ServerState::new(listener.waker()?));
         let server = HttpServer {
-            listener,
+            listener: Box::new(listener),
             handler: Arc::new(self),
+            state: state.clone(),
         };
-        server.run();
-        Ok(())
+        let thread = thread::spawn(move || server.run());
+        Ok(ServerHandle {
+            addr,
+            state,
+            thread,
+        })
     }
 }
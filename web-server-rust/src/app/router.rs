@@ -0,0 +1,209 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::method::Method;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// Every node is one path segment. On lookup static children are tried
+// first, then the `:param` child and finally the `*wildcard` child,
+// backtracking when a branch does not lead to a route for the method.
+struct Node<T> {
+    statics: HashMap<String, Node<T>>,
+    param: Option<(String, Box<Node<T>>)>,
+    wildcard: Option<(String, HashMap<Method, Arc<T>>)>,
+    routes: HashMap<Method, Arc<T>>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Self {
+            statics: HashMap::new(),
+            param: None,
+            wildcard: None,
+            routes: HashMap::new(),
+        }
+    }
+    fn insert(&mut self, segments: &[&str], method: Method, value: Arc<T>) {
+        let Some((segment, rest)) = segments.split_first() else {
+            self.routes.insert(method, value);
+            return;
+        };
+        if let Some(name) = segment.strip_prefix(':') {
+            let (param_name, node) = self
+                .param
+                .get_or_insert_with(|| (name.to_owned(), Box::new(Node::new())));
+            assert!(
+                param_name == name,
+                "conflicting parameter names `:{}` and `:{}`",
+                param_name,
+                name
+            );
+            node.insert(rest, method, value);
+        } else if let Some(name) = segment.strip_prefix('*') {
+            assert!(
+                rest.is_empty(),
+                "wildcard `*{}` must be the last segment",
+                name
+            );
+            let (wildcard_name, routes) = self
+                .wildcard
+                .get_or_insert_with(|| (name.to_owned(), HashMap::new()));
+            assert!(
+                wildcard_name == name,
+                "conflicting wildcard names `*{}` and `*{}`",
+                wildcard_name,
+                name
+            );
+            routes.insert(method, value);
+        } else {
+            self.statics
+                .entry(segment.to_string())
+                .or_insert_with(Node::new)
+                .insert(rest, method, value);
+        }
+    }
+    // Walks the branches matching `segments` in priority order until `f`
+    // accepts the routes of one, `params` then holds its parameters.
+    fn walk<'a, F>(
+        &'a self,
+        segments: &[&str],
+        params: &mut Vec<(String, String)>,
+        f: &mut F,
+    ) -> bool
+    where
+        F: FnMut(&'a HashMap<Method, Arc<T>>) -> bool,
+    {
+        let Some((segment, rest)) = segments.split_first() else {
+            return !self.routes.is_empty() && f(&self.routes);
+        };
+        if let Some(node) = self.statics.get(*segment)
+            && node.walk(rest, params, f)
+        {
+            return true;
+        }
+        if let Some((name, node)) = &self.param
+            && !segment.is_empty()
+        {
+            params.push((name.clone(), segment.to_string()));
+            if node.walk(rest, params, f) {
+                return true;
+            }
+            params.pop();
+        }
+        if let Some((name, routes)) = &self.wildcard {
+            params.push((name.clone(), segments.join("/")));
+            if f(routes) {
+                return true;
+            }
+            params.pop();
+        }
+        false
+    }
+}
+
+pub(crate) enum Route<'a, T> {
+    Found(&'a Arc<T>, Params),
+    // The path matches, but not with this method. Holds the methods that
+    // would, for the `Allow` header.
+    MethodNotAllowed(Vec<Method>),
+    NotFound,
+}
+
+pub(crate) struct Router<T> {
+    root: Node<T>,
+}
+
+impl<T> Router<T> {
+    pub(crate) fn new() -> Self {
+        Self { root: Node::new() }
+    }
+    // Ex: /users/:id, /files/*rest, /posts/:id/:slug?
+    pub(crate) fn insert(&mut self, method: Method, path: &str, value: T) {
+        let value = Arc::new(value);
+        let segments = split_path(path);
+        let optional = segments.iter().filter(|s| s.ends_with('?')).count();
+        assert!(optional < 16, "too many optional segments in `{}`", path);
+        // Register every combination of present / absent optional segments.
+        for mask in 0..(1u32 << optional) {
+            let mut bit = 0;
+            let mut route = Vec::with_capacity(segments.len());
+            for segment in &segments {
+                if let Some(segment) = segment.strip_suffix('?') {
+                    if mask & (1 << bit) == 0 {
+                        route.push(segment);
+                    }
+                    bit += 1;
+                } else {
+                    route.push(segment);
+                }
+            }
+            if route.is_empty() {
+                route.push("");
+            }
+            self.root.insert(&route, method.clone(), value.clone());
+        }
+    }
+    // A route for another method does not hide a less specific one for
+    // this method. Ex: `DELETE /users/new` matches `DELETE /users/:id` even
+    // with `GET /users/new` registered.
+    pub(crate) fn find(&self, method: &Method, path: &str) -> Route<'_, T> {
+        let segments = split_path(path);
+        let mut params = Vec::new();
+        let mut found = None;
+        let matched = self.root.walk(&segments, &mut params, &mut |routes| {
+            // A `GET` route answers `HEAD` too.
+            found = routes.get(method).or_else(|| match method {
+                Method::Head => routes.get(&Method::Get),
+                _ => None,
+            });
+            found.is_some()
+        });
+        if let (true, Some(value)) = (matched, found) {
+            return Route::Found(value, Params(params));
+        }
+        let mut allow = Vec::new();
+        self.root.walk(&segments, &mut Vec::new(), &mut |routes| {
+            for method in routes.keys() {
+                if !allow.contains(method) {
+                    allow.push(method.clone());
+                }
+            }
+            false
+        });
+        if allow.is_empty() {
+            return Route::NotFound;
+        }
+        if allow.contains(&Method::Get) && !allow.contains(&Method::Head) {
+            allow.push(Method::Head);
+        }
+        allow.sort_by_key(|m| m.to_string());
+        Route::MethodNotAllowed(allow)
+    }
+}
+
+#[inline]
+fn split_path(path: &str) -> Vec<&str> {
+    // `/` -> [""], `/a/b` -> ["a", "b"], `/a/` -> ["a", ""]
+    path.strip_prefix('/').unwrap_or(path).split('/').collect()
+}
@@ -0,0 +1,259 @@
+use std::{
+    io::{self, BufRead},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    error::HttpError,
+    header::{self, HeaderMap},
+};
+
+pub const DEFAULT_BODY_LIMIT: usize = 1024 * 1024;
+pub const DEFAULT_FORM_LIMIT: usize = 64 * 1024;
+pub const DEFAULT_FORM_FIELDS: usize = 1000;
+pub const DEFAULT_MULTIPART_LIMIT: usize = 32 * 1024 * 1024;
+pub const DEFAULT_PART_LIMIT: usize = 8 * 1024 * 1024;
+pub const DEFAULT_PART_MEMORY: usize = 64 * 1024;
+
+// Limits for parsed bodies, on top of the body limit.
+#[derive(Debug, Clone)]
+pub(crate) struct Limits {
+    pub(crate) form_size: usize,
+    // Also the most parts in a multipart body.
+    pub(crate) form_fields: usize,
+    // Replaces the body limit for multipart bodies.
+    pub(crate) multipart_size: usize,
+    pub(crate) part_size: usize,
+    // Spooled parts larger than this go to a temporary file.
+    pub(crate) part_memory: usize,
+    // Where those files go, the system's temporary directory if `None`.
+    pub(crate) upload_dir: Option<Arc<Path>>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            form_size: DEFAULT_FORM_LIMIT,
+            form_fields: DEFAULT_FORM_FIELDS,
+            multipart_size: DEFAULT_MULTIPART_LIMIT,
+            part_size: DEFAULT_PART_LIMIT,
+            part_memory: DEFAULT_PART_MEMORY,
+            upload_dir: None,
+        }
+    }
+}
+
+// A body framed both ways is how requests get smuggled past a proxy that
+// reads the other header, refuse it (RFC 9112 section 6.1). Same for a
+// length another parser could read differently.
+// Ex: "Content-Length: 5" twice is fine, "5, 6", "+5" or " 5 5" are not
+pub(crate) fn check_framing(header: &HeaderMap) -> Result<(), HttpError> {
+    if header.contains_key(header::TRANSFER_ENCODING) && header.contains_key(header::CONTENT_LENGTH)
+    {
+        return Err(HttpError::BadRequest);
+    }
+    let mut lengths = header.get_all(header::CONTENT_LENGTH).map(str::trim);
+    if let Some(first) = lengths.next() {
+        let digits = !first.is_empty() && first.bytes().all(|b| b.is_ascii_digit());
+        if !digits || first.parse::<u64>().is_err() || lengths.any(|len| len != first) {
+            return Err(HttpError::BadRequest);
+        }
+    }
+    // Without `chunked` last the body has no end, anything before it would
+    // reach the application still encoded.
+    // Ex: "chunked" is fine, "gzip, chunked" is 501, "chunked, gzip" is 400
+    let codings: Vec<_> = header
+        .get_all(header::TRANSFER_ENCODING)
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty())
+        .collect();
+    if let Some((last, rest)) = codings.split_last() {
+        if !last.eq_ignore_ascii_case("chunked") {
+            return Err(HttpError::BadRequest);
+        }
+        if !rest.is_empty() {
+            return Err(HttpError::UnsupportedTransferEncoding);
+        }
+    } else if header.contains_key(header::TRANSFER_ENCODING) {
+        return Err(HttpError::BadRequest);
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Kind {
+    Length(u64),
+    Chunked { remaining: u64, first: bool },
+    Invalid,
+    Done,
+}
+
+#[derive(Debug)]
+pub(crate) struct BodyState {
+    kind: Kind,
+    limit: usize,
+    read: usize,
+}
+
+impl BodyState {
+    pub(crate) fn new(content_length: Option<&str>, transfer_encoding: Option<&str>) -> Self {
+        let kind = match (transfer_encoding, content_length) {
+            (Some(te), _) => {
+                // Only `chunked` is understood, `check_framing` refuses the rest.
+                match te.rsplit(',').next().map(str::trim) {
+                    Some(v) if v.eq_ignore_ascii_case("chunked") => Kind::Chunked {
+                        remaining: 0,
+                        first: true,
+                    },
+                    _ => Kind::Invalid,
+                }
+            }
+            (None, Some(len)) => match len.trim().parse::<u64>() {
+                Ok(0) => Kind::Done,
+                Ok(len) => Kind::Length(len),
+                Err(_) => Kind::Invalid,
+            },
+            (None, None) => Kind::Done,
+        };
+        Self {
+            kind,
+            limit: DEFAULT_BODY_LIMIT,
+            read: 0,
+        }
+    }
+    #[inline]
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+    pub(crate) fn read<R: BufRead>(
+        &mut self,
+        r: &mut R,
+        buf: &mut [u8],
+    ) -> Result<usize, HttpError> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.kind {
+            Kind::Done => Ok(0),
+            Kind::Invalid => Err(HttpError::MalformedBody),
+            Kind::Length(remaining) => {
+                if self.read as u64 + remaining > self.limit as u64 {
+                    return Err(HttpError::ContentTooLarge);
+                }
+                let max = buf.len().min(remaining as usize);
+                let n = r.read(&mut buf[..max])?;
+                if n == 0 {
+                    return Err(HttpError::MalformedBody);
+                }
+                self.read += n;
+                self.kind = match remaining - n as u64 {
+                    0 => Kind::Done,
+                    v => Kind::Length(v),
+                };
+                Ok(n)
+            }
+            Kind::Chunked { remaining, first } => {
+                let remaining = if remaining == 0 {
+                    if !first {
+                        expect_crlf(r)?;
+                    }
+                    let size = read_chunk_size(r)?;
+                    if size == 0 {
+                        skip_trailers(r)?;
+                        self.kind = Kind::Done;
+                        return Ok(0);
+                    }
+                    size
+                } else {
+                    remaining
+                };
+                if self.read as u64 + remaining > self.limit as u64 {
+                    return Err(HttpError::ContentTooLarge);
+                }
+                let max = buf.len().min(remaining as usize);
+                let n = r.read(&mut buf[..max])?;
+                if n == 0 {
+                    return Err(HttpError::MalformedBody);
+                }
+                self.read += n;
+                self.kind = Kind::Chunked {
+                    remaining: remaining - n as u64,
+                    first: false,
+                };
+                Ok(n)
+            }
+        }
+    }
+}
+
+// Chunk size lines are tiny, refuse anything absurd.
+const MAX_CHUNK_LINE: u64 = 4096;
+// Trailers are capped like the header section.
+const MAX_TRAILER_SIZE: u64 = 64 * 1024;
+
+// Only CRLF ends a line, a bare LF is how a body gets read differently by
+// a proxy in front of us.
+fn read_line<R: BufRead>(r: &mut R, limit: u64) -> Result<String, HttpError> {
+    let mut line = Vec::new();
+    let n = io::Read::take(&mut *r, limit + 2).read_until(b'\n', &mut line)?;
+    if !line.ends_with(b"\r\n") {
+        return Err(if n as u64 > limit {
+            HttpError::HeaderTooLarge
+        } else {
+            HttpError::MalformedBody
+        });
+    }
+    line.truncate(line.len() - 2);
+    String::from_utf8(line).map_err(|_| HttpError::MalformedBody)
+}
+
+fn read_chunk_line<R: BufRead>(r: &mut R) -> Result<String, HttpError> {
+    read_line(r, MAX_CHUNK_LINE).map_err(|err| match err {
+        HttpError::HeaderTooLarge => HttpError::MalformedBody,
+        err => err,
+    })
+}
+
+fn expect_crlf<R: BufRead>(r: &mut R) -> Result<(), HttpError> {
+    if read_chunk_line(r)?.is_empty() {
+        Ok(())
+    } else {
+        Err(HttpError::MalformedBody)
+    }
+}
+
+fn read_chunk_size<R: BufRead>(r: &mut R) -> Result<u64, HttpError> {
+    let line = read_chunk_line(r)?;
+    // Ex: 1a;name=value
+    let size = line.split(';').next().unwrap_or_default().trim();
+    if size.is_empty() {
+        return Err(HttpError::MalformedBody);
+    }
+    u64::from_str_radix(size, 16).map_err(|_| HttpError::MalformedBody)
+}
+
+fn skip_trailers<R: BufRead>(r: &mut R) -> Result<(), HttpError> {
+    let mut size = 0;
+    loop {
+        let line = read_line(r, MAX_TRAILER_SIZE.saturating_sub(size))?;
+        if line.is_empty() {
+            return Ok(());
+        }
+        size += line.len() as u64 + 2;
+    }
+}
+
+pub struct BodyReader<'a, R> {
+    pub(crate) reader: &'a Mutex<R>,
+    pub(crate) state: &'a Mutex<BodyState>,
+}
+
+impl<R: BufRead> io::Read for BodyReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut reader = self.reader.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        state.read(&mut *reader, buf).map_err(io::Error::from)
+    }
+}
@@ -0,0 +1,142 @@
+use std::time::SystemTime;
+
+use crate::{
+    date,
+    header::{self, HeaderMap},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Precondition {
+    Pass,
+    NotModified,
+    Failed,
+}
+
+// RFC 9110 section 13.2.2 for a `GET` or `HEAD` request, against the
+// validators of the response about to be sent.
+pub(crate) fn evaluate(
+    request: &HeaderMap,
+    etag: Option<&str>,
+    modified: Option<SystemTime>,
+) -> Precondition {
+    let modified = modified.map(date::truncate);
+    if let Some(value) = request.get(header::IF_MATCH) {
+        if !matches(value, etag, true) {
+            return Precondition::Failed;
+        }
+    } else if let Some(since) = request
+        .get(header::IF_UNMODIFIED_SINCE)
+        .and_then(date::parse_http_date)
+        // Nothing to compare without a modification date, ignored then.
+        && modified.is_some_and(|modified| modified > since)
+    {
+        return Precondition::Failed;
+    }
+    if let Some(value) = request.get(header::IF_NONE_MATCH) {
+        if matches(value, etag, false) {
+            return Precondition::NotModified;
+        }
+    } else if let Some(since) = request
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(date::parse_http_date)
+        && modified.is_some_and(|modified| modified <= since)
+    {
+        return Precondition::NotModified;
+    }
+    Precondition::Pass
+}
+
+// Ex: "*", W/"a", "b"
+fn matches(value: &str, etag: Option<&str>, strong: bool) -> bool {
+    // There is a representation, the one being sent.
+    if value.trim() == "*" {
+        return true;
+    }
+    let Some(etag) = etag else {
+        return false;
+    };
+    let opaque = |tag: &str| match tag.strip_prefix("W/") {
+        Some(tag) => (true, tag.to_owned()),
+        None => (false, tag.to_owned()),
+    };
+    let (weak, etag) = opaque(etag.trim());
+    value
+        .split(',')
+        .map(|tag| opaque(tag.trim()))
+        .any(|(w, tag)| tag == etag && !(strong && (w || weak)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{Precondition, evaluate};
+    use crate::header::HeaderMap;
+
+    #[test]
+    fn preconditions() {
+        let modified = Some(UNIX_EPOCH + Duration::from_millis(784_111_777_500));
+        let check = |name: &str, value: &str, etag: &str| {
+            let mut request = HeaderMap::new();
+            request.insert(name, value);
+            evaluate(&request, Some(etag), modified)
+        };
+        assert_eq!(
+            check("If-None-Match", "\"a\", W/\"b\"", "W/\"b\""),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            check("If-None-Match", "*", "\"b\""),
+            Precondition::NotModified
+        );
+        assert_eq!(check("If-None-Match", "\"c\"", "\"b\""), Precondition::Pass);
+        assert_eq!(check("If-Match", "\"b\"", "\"b\""), Precondition::Pass);
+        assert_eq!(
+            check("If-Match", "W/\"b\"", "W/\"b\""),
+            Precondition::Failed
+        );
+        assert_eq!(check("If-Match", "\"c\"", "\"b\""), Precondition::Failed);
+        let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+        assert_eq!(
+            check("If-Modified-Since", date, "\"b\""),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            check(
+                "If-Modified-Since",
+                "Sun, 06 Nov 1994 08:49:36 GMT",
+                "\"b\""
+            ),
+            Precondition::Pass
+        );
+        assert_eq!(
+            check("If-Unmodified-Since", date, "\"b\""),
+            Precondition::Pass
+        );
+        assert_eq!(
+            check(
+                "If-Unmodified-Since",
+                "Sun, 06 Nov 1994 08:49:36 GMT",
+                "\"b\""
+            ),
+            Precondition::Failed
+        );
+        assert_eq!(
+            check("If-Modified-Since", "garbage", "\"b\""),
+            Precondition::Pass
+        );
+
+        let mut request = HeaderMap::new();
+        request.insert("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:36 GMT");
+        assert_eq!(evaluate(&request, Some("\"b\""), None), Precondition::Pass);
+
+        // If-None-Match wins over If-Modified-Since.
+        let mut request = HeaderMap::new();
+        request.insert("If-None-Match", "\"c\"");
+        request.insert("If-Modified-Since", date);
+        assert_eq!(
+            evaluate(&request, Some("\"b\""), modified),
+            Precondition::Pass
+        );
+    }
+}
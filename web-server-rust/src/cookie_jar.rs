@@ -0,0 +1,144 @@
+use std::{fmt, io};
+
+use crate::{
+    cookie::Cookie,
+    crypto::{base64_decode, base64_encode, hmac_sha256, hmac_sha256_verify},
+    error::HttpError,
+    request::HttpRequest,
+    response::HttpResponse,
+};
+
+#[cfg(feature = "crypto")]
+use crate::crypto::{self, NONCE_LEN};
+
+// Poly1305 tag at the end of every encrypted value.
+#[cfg(feature = "crypto")]
+const TAG_LEN: usize = 16;
+
+#[derive(Clone)]
+struct Keys {
+    signing: [u8; 32],
+    #[cfg(feature = "crypto")]
+    encryption: [u8; 32],
+}
+
+impl Keys {
+    // Independent keys per use, so a signature key never encrypts.
+    fn derive(secret: &[u8]) -> Self {
+        assert!(
+            secret.len() >= 32,
+            "cookie secrets must be at least 32 bytes long"
+        );
+        Self {
+            signing: hmac_sha256(secret, &[b"cookie signing"]),
+            #[cfg(feature = "crypto")]
+            encryption: hmac_sha256(secret, &[b"cookie encryption"]),
+        }
+    }
+}
+
+// Protects cookie values with a server secret. Signed cookies can be read
+// but not changed by the client, private ones can't be read either. Values
+// that fail verification are treated as absent. Private cookies need the
+// `crypto` feature.
+// Ex:
+//     let jar = CookieJar::new(&new_secret).old_key(&previous_secret);
+//     jar.set_signed(&res, Cookie::new("user", "42").path("/"))?;
+//     let user = jar.signed(&req, "user");
+#[derive(Clone)]
+pub struct CookieJar {
+    // Newest first, only that one signs and encrypts.
+    keys: Vec<Keys>,
+}
+
+impl CookieJar {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            keys: vec![Keys::derive(secret)],
+        }
+    }
+    // Keeps accepting cookies made with a retired secret.
+    pub fn old_key(mut self, secret: &[u8]) -> Self {
+        self.keys.push(Keys::derive(secret));
+        self
+    }
+    // Ex: 42.<base64 HMAC of "user=42">
+    pub fn sign(&self, name: &str, value: &str) -> String {
+        let mac = hmac_sha256(
+            &self.keys[0].signing,
+            &[name.as_bytes(), b"=", value.as_bytes()],
+        );
+        format!("{}.{}", value, base64_encode(&mac))
+    }
+    pub fn verify<'a>(&self, name: &str, signed: &'a str) -> Option<&'a str> {
+        let (value, mac) = signed.rsplit_once('.')?;
+        let mac = base64_decode(mac)?;
+        let message: &[&[u8]] = &[name.as_bytes(), b"=", value.as_bytes()];
+        self.keys
+            .iter()
+            .any(|keys| hmac_sha256_verify(&keys.signing, message, &mac))
+            .then_some(value)
+    }
+    // ChaCha20-Poly1305 with a random nonce, the cookie name is
+    // authenticated too. Ex: <base64 of nonce, ciphertext and tag>
+    #[cfg(feature = "crypto")]
+    pub fn encrypt(&self, name: &str, value: &str) -> String {
+        let mut nonce = [0u8; NONCE_LEN];
+        crypto::random_bytes(&mut nonce);
+        let sealed = crypto::seal(
+            &self.keys[0].encryption,
+            &nonce,
+            name.as_bytes(),
+            value.as_bytes(),
+        );
+        base64_encode(&[&nonce[..], &sealed].concat())
+    }
+    #[cfg(feature = "crypto")]
+    pub fn decrypt(&self, name: &str, encrypted: &str) -> Option<String> {
+        let raw = base64_decode(encrypted)?;
+        if raw.len() < NONCE_LEN + TAG_LEN {
+            return None;
+        }
+        let (nonce, sealed) = raw.split_at(NONCE_LEN);
+        let nonce = nonce.try_into().unwrap();
+        let plain = self
+            .keys
+            .iter()
+            .find_map(|keys| crypto::open(&keys.encryption, nonce, name.as_bytes(), sealed))?;
+        String::from_utf8(plain).ok()
+    }
+    pub fn signed<'a, R: io::Read>(&self, req: &'a HttpRequest<R>, name: &str) -> Option<&'a str> {
+        self.verify(name, req.cookie(name)?)
+    }
+    #[cfg(feature = "crypto")]
+    pub fn private<R: io::Read>(&self, req: &HttpRequest<R>, name: &str) -> Option<String> {
+        self.decrypt(name, req.cookie(name)?)
+    }
+    pub fn set_signed<W: io::Write>(
+        &self,
+        res: &HttpResponse<W>,
+        mut cookie: Cookie,
+    ) -> Result<(), HttpError> {
+        cookie.value = self.sign(&cookie.name, &cookie.value);
+        res.set_cookie(cookie)?;
+        Ok(())
+    }
+    #[cfg(feature = "crypto")]
+    pub fn set_private<W: io::Write>(
+        &self,
+        res: &HttpResponse<W>,
+        mut cookie: Cookie,
+    ) -> Result<(), HttpError> {
+        cookie.value = self.encrypt(&cookie.name, &cookie.value);
+        res.set_cookie(cookie)?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CookieJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieJar")
+            .field("keys", &self.keys.len())
+            .finish()
+    }
+}
@@ -0,0 +1,241 @@
+// Cryptography for signed and encrypted cookies, session IDs and `ETag`s.
+// The `crypto` feature brings in the RustCrypto SHA-256, HMAC and
+// ChaCha20-Poly1305, without it signing falls back to an unaudited SHA-256
+// of our own and encrypted cookies are unavailable.
+
+#[cfg(feature = "crypto")]
+mod rustcrypto;
+#[cfg(not(feature = "crypto"))]
+mod sha256;
+
+use std::io;
+
+#[cfg(feature = "crypto")]
+pub(crate) use rustcrypto::{NONCE_LEN, hmac_sha256, hmac_sha256_verify, open, seal, sha256};
+#[cfg(not(feature = "crypto"))]
+pub(crate) use sha256::{hmac_sha256, hmac_sha256_verify, sha256};
+
+// Random bytes from the operating system. Keys, nonces and session IDs
+// must never come from anything weaker, so there is no fallback.
+pub(crate) fn random_bytes(buf: &mut [u8]) {
+    if let Err(err) = os_random(buf) {
+        panic!("the operating system gave no random bytes: {}", err);
+    }
+}
+
+#[cfg(unix)]
+fn os_random(buf: &mut [u8]) -> io::Result<()> {
+    unsafe extern "C" {
+        fn getentropy(buf: *mut std::ffi::c_void, len: usize) -> std::ffi::c_int;
+    }
+    // Ex: getrandom(2) on Linux, at most 256 bytes per call.
+    for chunk in buf.chunks_mut(256) {
+        if unsafe { getentropy(chunk.as_mut_ptr().cast(), chunk.len()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn os_random(buf: &mut [u8]) -> io::Result<()> {
+    const BCRYPT_USE_SYSTEM_PREFERRED_RNG: u32 = 2;
+    #[link(name = "bcrypt")]
+    unsafe extern "system" {
+        fn BCryptGenRandom(
+            algorithm: *mut std::ffi::c_void,
+            buf: *mut u8,
+            len: u32,
+            flags: u32,
+        ) -> i32;
+    }
+    for chunk in buf.chunks_mut(u32::MAX as usize) {
+        let status = unsafe {
+            BCryptGenRandom(
+                std::ptr::null_mut(),
+                chunk.as_mut_ptr(),
+                chunk.len() as u32,
+                BCRYPT_USE_SYSTEM_PREFERRED_RNG,
+            )
+        };
+        if status != 0 {
+            return Err(io::Error::other(format!(
+                "BCryptGenRandom failed with {:#x}",
+                status
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn os_random(_: &mut [u8]) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// URL-safe alphabet without padding, every character is a valid cookie octet.
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    out
+}
+
+pub(crate) fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    if input.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    for chunk in input.as_bytes().chunks(4) {
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let v = BASE64.iter().position(|b| b == c)? as u32;
+            n |= v << (18 - 6 * i);
+        }
+        let bytes = n.to_be_bytes();
+        // Bits past the last byte must be zero, one encoding per input.
+        if bytes[chunk.len()..].iter().any(|b| *b != 0) {
+            return None;
+        }
+        out.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        base64_decode, base64_encode, hmac_sha256, hmac_sha256_verify, random_bytes, sha256,
+    };
+
+    #[test]
+    fn random() {
+        let mut a = [0u8; 300];
+        let mut b = [0u8; 300];
+        random_bytes(&mut a);
+        random_bytes(&mut b);
+        assert_ne!(a, b);
+        // The chunk past the first 256 bytes is filled too.
+        assert_ne!(a[256..], [0; 44]);
+    }
+
+    #[test]
+    fn base64() {
+        for (plain, encoded) in [
+            ("", ""),
+            ("f", "Zg"),
+            ("fo", "Zm8"),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg"),
+            ("\u{ff}\u{fe}", "w7_Dvg"),
+        ] {
+            assert_eq!(base64_encode(plain.as_bytes()), encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), plain.as_bytes());
+        }
+        assert!(base64_decode("Zm9v=").is_none());
+        assert!(base64_decode("Zh").is_none());
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn digest() {
+        assert_eq!(
+            hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    // RFC 4231, test cases 2 and 6.
+    #[test]
+    fn hmac() {
+        assert_eq!(
+            hex(&hmac_sha256(
+                b"Jefe",
+                &[b"what do ya want ", b"for nothing?"]
+            )),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(
+                &[0xaa; 131],
+                &[b"Test Using Larger Than Block-Size Key - Hash Key First"]
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // RFC 4231, test cases 1 to 4, 6 and 7.
+    #[test]
+    fn hmac_verify() {
+        for (key, data, tag) in [
+            (
+                vec![0x0b; 20],
+                b"Hi There".to_vec(),
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?".to_vec(),
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                vec![0xaa; 20],
+                vec![0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                (1..=25).collect(),
+                vec![0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (
+                vec![0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                vec![0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than \
+                  block-size data. The key needs to be hashed before being used by the \
+                  HMAC algorithm."
+                    .to_vec(),
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ] {
+            let tag = unhex(tag);
+            assert!(hmac_sha256_verify(&key, &[&data], &tag));
+            for i in 0..tag.len() {
+                let mut forged = tag.clone();
+                forged[i] ^= 1;
+                assert!(!hmac_sha256_verify(&key, &[&data], &forged));
+            }
+            assert!(!hmac_sha256_verify(&key, &[&data], &tag[..16]));
+            assert!(!hmac_sha256_verify(&key, &[&data], &[]));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+// The RustCrypto implementations, enabled by the `crypto` feature.
+
+use chacha20poly1305::{
+    ChaCha20Poly1305, KeyInit,
+    aead::{Aead, Payload},
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+pub(crate) const NONCE_LEN: usize = 12;
+
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+fn mac(key: &[u8], parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+// Ex: hmac_sha256(key, &[b"name", b"=", b"value"])
+pub(crate) fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    mac(key, parts).finalize().into_bytes().into()
+}
+
+// `verify_slice` compares in constant time and refuses truncated tags.
+pub(crate) fn hmac_sha256_verify(key: &[u8], parts: &[&[u8]], tag: &[u8]) -> bool {
+    mac(key, parts).verify_slice(tag).is_ok()
+}
+
+// ChaCha20-Poly1305, `aad` is authenticated along with the ciphertext but
+// not encrypted. A nonce must never be used twice with the same key.
+pub(crate) fn seal(key: &[u8; 32], nonce: &[u8; NONCE_LEN], aad: &[u8], plain: &[u8]) -> Vec<u8> {
+    ChaCha20Poly1305::new(key.into())
+        .encrypt(nonce.into(), Payload { msg: plain, aad })
+        .expect("ChaCha20-Poly1305 encrypts anything shorter than 256 GiB")
+}
+
+pub(crate) fn open(
+    key: &[u8; 32],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Option<Vec<u8>> {
+    ChaCha20Poly1305::new(key.into())
+        .decrypt(nonce.into(), Payload { msg: sealed, aad })
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open, seal};
+
+    #[test]
+    fn seal_and_open() {
+        let key = [7; 32];
+        let nonce = [1; 12];
+        let sealed = seal(&key, &nonce, b"name", b"value");
+        assert_eq!(sealed.len(), 5 + 16);
+        assert_eq!(open(&key, &nonce, b"name", &sealed).unwrap(), b"value");
+        assert!(open(&key, &nonce, b"other", &sealed).is_none());
+        assert!(open(&[8; 32], &nonce, b"name", &sealed).is_none());
+        assert!(open(&key, &[2; 12], b"name", &sealed).is_none());
+        let mut forged = sealed.clone();
+        forged[0] ^= 1;
+        assert!(open(&key, &nonce, b"name", &forged).is_none());
+    }
+}
@@ -1,10 +1,25 @@
 use std::{fmt, io};
 
+use crate::Status;
+
 #[derive(Debug)]
 pub enum HttpError {
     InvalidMethod,
+    NotFound,
+    MethodNotAllowed,
     InvalidHttpVersion,
     BadRequest,
+    MalformedBody,
+    ContentTooLarge,
+    UriTooLong,
+    HeaderTooLarge,
+    InvalidHeader,
+    InvalidJson,
+    UnsupportedMediaType,
+    TooManyFields,
+    PartTooLarge,
+    MultipartTooLarge,
+    UnsupportedTransferEncoding,
     StdError(io::Error),
 }
 
@@ -12,17 +27,69 @@ impl fmt::Display for HttpError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InvalidMethod => write!(f, "Invalid Method"),
+            Self::MethodNotAllowed => write!(f, "Method Not Allowed"),
             Self::InvalidHttpVersion => write!(f, "Invalid Http Version"),
             Self::BadRequest => write!(f, "Bad Request"),
+            Self::MalformedBody => write!(f, "Malformed Body"),
+            Self::ContentTooLarge => write!(f, "Content Too Large"),
+            Self::UriTooLong => write!(f, "URI Too Long"),
+            Self::HeaderTooLarge => write!(f, "Header Too Large"),
+            Self::InvalidHeader => write!(f, "Invalid Header"),
+            Self::InvalidJson => write!(f, "Invalid JSON"),
+            Self::NotFound => write!(f, "Not Found"),
+            Self::UnsupportedMediaType => write!(f, "Unsupported Media Type"),
+            Self::TooManyFields => write!(f, "Too Many Fields"),
+            Self::PartTooLarge => write!(f, "Part Too Large"),
+            Self::MultipartTooLarge => write!(f, "Multipart Body Too Large"),
+            Self::UnsupportedTransferEncoding => write!(f, "Unsupported Transfer Encoding"),
             Self::StdError(err) => write!(f, "{}", err),
         }
     }
 }
 
+impl HttpError {
+    pub fn status(&self) -> Status {
+        match self {
+            Self::InvalidMethod | Self::UnsupportedTransferEncoding => Status::NotImplemented,
+            Self::MethodNotAllowed => Status::MethodNotAllowed,
+            Self::NotFound => Status::NotFound,
+            Self::InvalidHttpVersion => Status::HTTPVersionNotSupported,
+            Self::BadRequest | Self::MalformedBody | Self::InvalidJson => Status::BadRequest,
+            Self::ContentTooLarge
+            | Self::TooManyFields
+            | Self::PartTooLarge
+            | Self::MultipartTooLarge => Status::ContentTooLarge,
+            Self::UnsupportedMediaType => Status::UnsupportedMediaType,
+            Self::UriTooLong => Status::URITooLong,
+            Self::HeaderTooLarge => Status::RequestHeaderFieldsTooLarge,
+            // Only raised for headers the application sets.
+            Self::InvalidHeader => Status::InternalServerError,
+            Self::StdError(err) => match err.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Status::RequestTimeout,
+                _ => Status::InternalServerError,
+            },
+        }
+    }
+}
+
 impl From<io::Error> for HttpError {
     fn from(value: io::Error) -> Self {
+        // Errors raised while reading a body travel through `io::Read`
+        // wrapped in an `io::Error`, unwrap them back here.
+        if value.get_ref().is_some_and(|inner| inner.is::<HttpError>()) {
+            return *value.into_inner().unwrap().downcast::<HttpError>().unwrap();
+        }
         Self::StdError(value)
     }
 }
 
+impl From<HttpError> for io::Error {
+    fn from(value: HttpError) -> Self {
+        match value {
+            HttpError::StdError(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
 impl std::error::Error for HttpError {}
@@ -0,0 +1,163 @@
+use crate::error::HttpError;
+
+pub const ACCEPT: &str = "Accept";
+pub const ACCEPT_ENCODING: &str = "Accept-Encoding";
+pub const ACCEPT_LANGUAGE: &str = "Accept-Language";
+pub const ACCEPT_RANGES: &str = "Accept-Ranges";
+pub const ALLOW: &str = "Allow";
+pub const AUTHORIZATION: &str = "Authorization";
+pub const CACHE_CONTROL: &str = "Cache-Control";
+pub const CONNECTION: &str = "Connection";
+pub const CONTENT_DISPOSITION: &str = "Content-Disposition";
+pub const CONTENT_ENCODING: &str = "Content-Encoding";
+pub const CONTENT_LENGTH: &str = "Content-Length";
+pub const CONTENT_RANGE: &str = "Content-Range";
+pub const CONTENT_TYPE: &str = "Content-Type";
+pub const COOKIE: &str = "Cookie";
+pub const DATE: &str = "Date";
+pub const ETAG: &str = "ETag";
+pub const EXPIRES: &str = "Expires";
+pub const HOST: &str = "Host";
+pub const IF_MATCH: &str = "If-Match";
+pub const IF_MODIFIED_SINCE: &str = "If-Modified-Since";
+pub const IF_NONE_MATCH: &str = "If-None-Match";
+pub const IF_RANGE: &str = "If-Range";
+pub const IF_UNMODIFIED_SINCE: &str = "If-Unmodified-Since";
+pub const LAST_MODIFIED: &str = "Last-Modified";
+pub const LOCATION: &str = "Location";
+pub const ORIGIN: &str = "Origin";
+pub const RANGE: &str = "Range";
+pub const REFERER: &str = "Referer";
+pub const SERVER: &str = "Server";
+pub const SET_COOKIE: &str = "Set-Cookie";
+pub const TRANSFER_ENCODING: &str = "Transfer-Encoding";
+pub const USER_AGENT: &str = "User-Agent";
+pub const VARY: &str = "Vary";
+pub const WWW_AUTHENTICATE: &str = "WWW-Authenticate";
+
+// Header fields in the order they were added. Names keep their case but are
+// compared case-insensitively, and a name may appear more than once.
+// Ex: map.get("content-type") finds `Content-Type`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // The first value.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+    #[inline]
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+    // Replaces every value of `name`.
+    pub fn insert<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        let name = name.into();
+        self.remove(&name);
+        self.entries.push((name, value.into()));
+    }
+    // Adds a value, keeping the existing ones. Ex: Set-Cookie
+    pub fn append<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        self.entries.push((name.into(), value.into()));
+    }
+    // Removes every value of `name`, returning the first one.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let mut removed = None;
+        self.entries.retain_mut(|(key, value)| {
+            if !key.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            if removed.is_none() {
+                removed = Some(std::mem::take(value));
+            }
+            false
+        });
+        removed
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// Ex: !#$%&'*+-.^_`|~ and alphanumerics
+pub(crate) fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+// A value with CR or LF would end the header early and let the rest be read
+// as headers or a body of its own.
+pub(crate) fn validate(name: &str, value: &str) -> Result<(), HttpError> {
+    if !is_token(name) || value.bytes().any(|b| matches!(b, b'\r' | b'\n' | 0)) {
+        return Err(HttpError::InvalidHeader);
+    }
+    Ok(())
+}
+
+// `Content-Type` style parameters, quoted values unescaped. The value of the
+// first item is empty.
+// Ex: multipart/form-data; boundary="a b"
+pub(crate) fn params(value: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    let mut rest = value;
+    let mut first = true;
+    std::iter::from_fn(move || {
+        rest = rest.trim_start_matches([' ', '\t', ';']);
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find([';', '=']).unwrap_or(rest.len());
+        let key = rest[..end].trim().to_owned();
+        rest = &rest[end..];
+        if first || !rest.starts_with('=') {
+            first = false;
+            return Some((key, String::new()));
+        }
+        rest = rest[1..].trim_start();
+        let mut value = String::new();
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let mut chars = quoted.char_indices();
+            rest = "";
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                    '"' => {
+                        rest = &quoted[i + 1..];
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+        } else {
+            let end = rest.find(';').unwrap_or(rest.len());
+            value = rest[..end].trim().to_owned();
+            rest = &rest[end..];
+        }
+        Some((key, value))
+    })
+}
@@ -1,12 +1,49 @@
 mod app;
+mod body;
+mod conditional;
+mod cookie;
+mod cookie_jar;
+mod crypto;
+mod date;
 mod error;
+pub mod header;
+mod json;
 mod method;
 mod mime;
+mod multipart;
+mod range;
 mod request;
 mod response;
+mod serve_dir;
 mod server;
+mod session;
 mod status;
+mod test_client;
+mod url;
 
-pub use app::App;
+pub use app::{App, Params};
+pub use body::{
+    BodyReader, DEFAULT_BODY_LIMIT, DEFAULT_FORM_FIELDS, DEFAULT_FORM_LIMIT,
+    DEFAULT_MULTIPART_LIMIT, DEFAULT_PART_LIMIT, DEFAULT_PART_MEMORY,
+};
+pub use cookie::{Cookie, SameSite};
+pub use cookie_jar::CookieJar;
+pub use error::HttpError;
+pub use header::HeaderMap;
+pub use json::Json;
+pub use method::Method;
 pub use mime::MimeType;
+pub use multipart::{Multipart, Part, Spooled, TempFile};
+pub use request::HttpRequest;
+pub use response::{ChunkedWriter, HttpResponse};
+pub use serve_dir::ServeDir;
+#[cfg(feature = "tls")]
+pub use server::TlsConfig;
+pub use server::{
+    Closer, Connection, Duplex, Listener, QueuePolicy, Reader, ServerHandle, ServerMetrics, Waker,
+    Writer,
+};
+pub use session::{FileStore, MemoryStore, Session, SessionData, SessionStore, Sessions};
 pub use status::Status;
+pub use test_client::{TestClient, TestRequest, TestResponse};
+pub use url::UrlEncoded;
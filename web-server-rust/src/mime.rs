@@ -1,27 +1,262 @@
-use std::fmt;
+use std::{fmt, path::Path, str::FromStr};
+
+use crate::{error::HttpError, header};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum MimeType {
     TextPlain,
     #[default]
     TextHtml,
+    TextCss,
+    TextJavascript,
+    TextCsv,
+    TextMarkdown,
     ImageJpg,
     ImagePng,
+    ImageGif,
+    ImageSvg,
+    ImageWebp,
+    ImageAvif,
+    ImageIcon,
+    AudioMpeg,
+    AudioOgg,
+    AudioWav,
     VideoMp4,
+    VideoWebm,
+    FontWoff,
+    FontWoff2,
+    FontTtf,
+    FontOtf,
     ApplicationJson,
     ApplicationPdf,
+    ApplicationXml,
+    ApplicationWasm,
+    ApplicationZip,
+    ApplicationGzip,
+    ApplicationOctetStream,
+    ApplicationFormUrlEncoded,
+    MultipartFormData,
+    // Anything else, written as it is, parameters included.
+    // Ex: MimeType::Custom("text/plain; charset=iso-8859-1".to_owned())
+    Custom(String),
+}
+
+// Every variant but `Custom`, for parsing.
+const KNOWN: [MimeType; 31] = [
+    MimeType::TextPlain,
+    MimeType::TextHtml,
+    MimeType::TextCss,
+    MimeType::TextJavascript,
+    MimeType::TextCsv,
+    MimeType::TextMarkdown,
+    MimeType::ImageJpg,
+    MimeType::ImagePng,
+    MimeType::ImageGif,
+    MimeType::ImageSvg,
+    MimeType::ImageWebp,
+    MimeType::ImageAvif,
+    MimeType::ImageIcon,
+    MimeType::AudioMpeg,
+    MimeType::AudioOgg,
+    MimeType::AudioWav,
+    MimeType::VideoMp4,
+    MimeType::VideoWebm,
+    MimeType::FontWoff,
+    MimeType::FontWoff2,
+    MimeType::FontTtf,
+    MimeType::FontOtf,
+    MimeType::ApplicationJson,
+    MimeType::ApplicationPdf,
+    MimeType::ApplicationXml,
+    MimeType::ApplicationWasm,
+    MimeType::ApplicationZip,
+    MimeType::ApplicationGzip,
+    MimeType::ApplicationOctetStream,
+    MimeType::ApplicationFormUrlEncoded,
+    MimeType::MultipartFormData,
+];
+
+impl MimeType {
+    // The type without parameters. Ex: text/html
+    pub fn essence(&self) -> &str {
+        match self {
+            Self::TextPlain => "text/plain",
+            Self::TextHtml => "text/html",
+            Self::TextCss => "text/css",
+            Self::TextJavascript => "text/javascript",
+            Self::TextCsv => "text/csv",
+            Self::TextMarkdown => "text/markdown",
+            Self::ImageJpg => "image/jpeg",
+            Self::ImagePng => "image/png",
+            Self::ImageGif => "image/gif",
+            Self::ImageSvg => "image/svg+xml",
+            Self::ImageWebp => "image/webp",
+            Self::ImageAvif => "image/avif",
+            Self::ImageIcon => "image/x-icon",
+            Self::AudioMpeg => "audio/mpeg",
+            Self::AudioOgg => "audio/ogg",
+            Self::AudioWav => "audio/wav",
+            Self::VideoMp4 => "video/mp4",
+            Self::VideoWebm => "video/webm",
+            Self::FontWoff => "font/woff",
+            Self::FontWoff2 => "font/woff2",
+            Self::FontTtf => "font/ttf",
+            Self::FontOtf => "font/otf",
+            Self::ApplicationJson => "application/json",
+            Self::ApplicationPdf => "application/pdf",
+            Self::ApplicationXml => "application/xml",
+            Self::ApplicationWasm => "application/wasm",
+            Self::ApplicationZip => "application/zip",
+            Self::ApplicationGzip => "application/gzip",
+            Self::ApplicationOctetStream => "application/octet-stream",
+            Self::ApplicationFormUrlEncoded => "application/x-www-form-urlencoded",
+            Self::MultipartFormData => "multipart/form-data",
+            Self::Custom(value) => value.split(';').next().unwrap().trim(),
+        }
+    }
+    // Text is always sent as UTF-8.
+    pub fn charset(&self) -> Option<String> {
+        match self {
+            Self::Custom(value) => header::params(value)
+                .skip(1)
+                .find(|(key, _)| key.eq_ignore_ascii_case("charset"))
+                .map(|(_, charset)| charset),
+            _ if self.essence().starts_with("text/") => Some("utf-8".to_owned()),
+            _ => None,
+        }
+    }
+    // Ex: "css" -> TextCss
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        Some(match ext.to_ascii_lowercase().as_str() {
+            "txt" | "text" => Self::TextPlain,
+            "html" | "htm" => Self::TextHtml,
+            "css" => Self::TextCss,
+            "js" | "mjs" => Self::TextJavascript,
+            "csv" => Self::TextCsv,
+            "md" | "markdown" => Self::TextMarkdown,
+            "jpg" | "jpeg" => Self::ImageJpg,
+            "png" => Self::ImagePng,
+            "gif" => Self::ImageGif,
+            "svg" => Self::ImageSvg,
+            "webp" => Self::ImageWebp,
+            "avif" => Self::ImageAvif,
+            "ico" => Self::ImageIcon,
+            "mp3" => Self::AudioMpeg,
+            "ogg" | "oga" | "opus" => Self::AudioOgg,
+            "wav" => Self::AudioWav,
+            "mp4" | "m4v" => Self::VideoMp4,
+            "webm" => Self::VideoWebm,
+            "woff" => Self::FontWoff,
+            "woff2" => Self::FontWoff2,
+            "ttf" => Self::FontTtf,
+            "otf" => Self::FontOtf,
+            "json" | "map" => Self::ApplicationJson,
+            "pdf" => Self::ApplicationPdf,
+            "xml" => Self::ApplicationXml,
+            "wasm" => Self::ApplicationWasm,
+            "zip" => Self::ApplicationZip,
+            "gz" => Self::ApplicationGzip,
+            "bin" => Self::ApplicationOctetStream,
+            _ => return None,
+        })
+    }
+    // Unknown extensions are `application/octet-stream`, so the browser
+    // never guesses, an uploaded file could pass as HTML.
+    // Ex: "static/app.css" -> TextCss
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_extension)
+            .unwrap_or(Self::ApplicationOctetStream)
+    }
 }
 
 impl fmt::Display for MimeType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::TextPlain => write!(f, "text/plain"),
-            Self::TextHtml => write!(f, "text/html"),
-            Self::ImageJpg => write!(f, "image/jpeg"),
-            Self::ImagePng => write!(f, "image/png"),
-            Self::VideoMp4 => write!(f, "video/mp4"),
-            Self::ApplicationJson => write!(f, "application/json"),
-            Self::ApplicationPdf => write!(f, "application/pdf"),
+            Self::Custom(value) => write!(f, "{}", value),
+            _ => match self.charset() {
+                Some(charset) => write!(f, "{}; charset={}", self.essence(), charset),
+                None => write!(f, "{}", self.essence()),
+            },
         }
     }
 }
+
+// Parses a `Content-Type` value. Known types with no parameters but a UTF-8
+// charset become their variant, everything else is kept as `Custom`.
+// Ex: "text/html; charset=UTF-8".parse::<MimeType>()
+impl FromStr for MimeType {
+    type Err = HttpError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let essence = s.split(';').next().unwrap().trim();
+        match essence.split_once('/') {
+            Some((kind, subtype)) if header::is_token(kind) && header::is_token(subtype) => {}
+            _ => return Err(HttpError::BadRequest),
+        }
+        let plain = header::params(s).skip(1).all(|(key, value)| {
+            key.eq_ignore_ascii_case("charset")
+                && (value.eq_ignore_ascii_case("utf-8") || value.eq_ignore_ascii_case("utf8"))
+        });
+        let known = KNOWN
+            .iter()
+            .find(|mime| mime.essence().eq_ignore_ascii_case(essence));
+        match known {
+            Some(mime) if plain => Ok(mime.clone()),
+            _ => Ok(Self::Custom(s.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MimeType;
+
+    #[test]
+    fn lookup() {
+        assert_eq!(MimeType::from_path("a/b/app.CSS"), MimeType::TextCss);
+        assert_eq!(MimeType::from_path("font.woff2"), MimeType::FontWoff2);
+        assert_eq!(
+            MimeType::from_path("archive.tar.unknown"),
+            MimeType::ApplicationOctetStream
+        );
+        assert_eq!(
+            MimeType::from_path("Makefile"),
+            MimeType::ApplicationOctetStream
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(MimeType::TextHtml.to_string(), "text/html; charset=utf-8");
+        assert_eq!(MimeType::ImageSvg.to_string(), "image/svg+xml");
+        assert_eq!(
+            MimeType::Custom("text/plain; charset=latin1".to_owned()).to_string(),
+            "text/plain; charset=latin1"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let parse = |s: &str| s.parse::<MimeType>();
+        assert_eq!(
+            parse("Text/HTML; charset=\"UTF-8\"").unwrap(),
+            MimeType::TextHtml
+        );
+        assert_eq!(
+            parse("application/json").unwrap(),
+            MimeType::ApplicationJson
+        );
+        let custom = parse("text/plain; charset=latin1").unwrap();
+        assert_eq!(custom.essence(), "text/plain");
+        assert_eq!(custom.charset().as_deref(), Some("latin1"));
+        assert_eq!(
+            parse("multipart/form-data; boundary=x").unwrap(),
+            MimeType::Custom("multipart/form-data; boundary=x".to_owned())
+        );
+        assert!(parse("text").is_err());
+        assert!(parse("text/ html").is_err());
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    body::{BodyReader, Limits},
+    crypto,
+    error::HttpError,
+    header::{self, HeaderMap},
+    url,
+};
+
+// Part headers are a few short lines, anything longer is an attack.
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+const READ_SIZE: usize = 8 * 1024;
+
+// A `multipart/form-data` body, read one part at a time straight from the
+// connection.
+// Ex:
+//     let mut form = req.multipart()?;
+//     while let Some(mut part) = form.next_part()? {
+//         if part.name() == "avatar" {
+//             part.spool()?.persist("uploads/avatar.png")?;
+//         }
+//     }
+pub struct Multipart<'a, R> {
+    body: BodyReader<'a, R>,
+    // Unread bytes start at `pos`.
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+    // `\r\n--boundary`, the boundary proper is always preceded by a line
+    // break, the first one included.
+    delimiter: Vec<u8>,
+    state: State,
+    parts: usize,
+    limits: Limits,
+}
+
+#[derive(PartialEq)]
+enum State {
+    // In the preamble or the data of a part.
+    Data,
+    // Right after a delimiter.
+    Delimiter,
+    Done,
+}
+
+impl<'a, R: io::BufRead> Multipart<'a, R> {
+    pub(crate) fn new(body: BodyReader<'a, R>, boundary: &str, limits: Limits) -> Self {
+        Self {
+            body,
+            // Lets the first delimiter match like all the others.
+            buf: b"\r\n".to_vec(),
+            pos: 0,
+            eof: false,
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            state: State::Data,
+            parts: 0,
+            limits,
+        }
+    }
+    // Skips whatever is left of the previous part.
+    pub fn next_part(&mut self) -> Result<Option<Part<'_, 'a, R>>, HttpError> {
+        while self.state == State::Data {
+            self.read_data(&mut [0; READ_SIZE])?;
+        }
+        if self.state == State::Done {
+            return Ok(None);
+        }
+        if self.starts_with(b"--")? {
+            // The epilogue is ignored like the preamble.
+            self.state = State::Done;
+            return Ok(None);
+        }
+        // Ex: --boundary  \r\n
+        let padding = self.read_line()?;
+        if !padding.iter().all(|b| *b == b' ' || *b == b'\t') {
+            return Err(HttpError::MalformedBody);
+        }
+        self.parts += 1;
+        if self.parts > self.limits.form_fields {
+            return Err(HttpError::TooManyFields);
+        }
+        let headers = self.read_headers()?;
+        self.state = State::Data;
+        let (name, filename) = headers
+            .get(header::CONTENT_DISPOSITION)
+            .and_then(parse_disposition)
+            .ok_or(HttpError::MalformedBody)?;
+        Ok(Some(Part {
+            multipart: self,
+            headers,
+            name,
+            filename,
+            read: 0,
+        }))
+    }
+    fn fill(&mut self) -> Result<(), HttpError> {
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        let len = self.buf.len();
+        self.buf.resize(len + READ_SIZE, 0);
+        let n = match self.body.read(&mut self.buf[len..]) {
+            Ok(n) => n,
+            Err(err) => {
+                self.buf.truncate(len);
+                return Err(match HttpError::from(err) {
+                    HttpError::ContentTooLarge => HttpError::MultipartTooLarge,
+                    err => err,
+                });
+            }
+        };
+        self.buf.truncate(len + n);
+        self.eof = n == 0;
+        Ok(())
+    }
+    fn starts_with(&mut self, prefix: &[u8]) -> Result<bool, HttpError> {
+        while self.buf.len() - self.pos < prefix.len() && !self.eof {
+            self.fill()?;
+        }
+        if self.buf[self.pos..].starts_with(prefix) {
+            self.pos += prefix.len();
+            return Ok(true);
+        }
+        Ok(false)
+    }
+    fn read_line(&mut self) -> Result<Vec<u8>, HttpError> {
+        loop {
+            if let Some(i) = find(&self.buf[self.pos..], b"\r\n") {
+                let line = self.buf[self.pos..self.pos + i].to_vec();
+                self.pos += i + 2;
+                return Ok(line);
+            }
+            if self.eof || self.buf.len() - self.pos > MAX_HEADER_SIZE {
+                return Err(HttpError::MalformedBody);
+            }
+            self.fill()?;
+        }
+    }
+    fn read_headers(&mut self) -> Result<HeaderMap, HttpError> {
+        let mut headers = HeaderMap::new();
+        let mut size = 0;
+        loop {
+            let line = self.read_line()?;
+            size += line.len() + 2;
+            if size > MAX_HEADER_SIZE {
+                return Err(HttpError::MalformedBody);
+            }
+            if line.is_empty() {
+                return Ok(headers);
+            }
+            let line = String::from_utf8(line).map_err(|_| HttpError::MalformedBody)?;
+            let (key, value) = line.split_once(':').ok_or(HttpError::MalformedBody)?;
+            if !header::is_token(key) {
+                return Err(HttpError::MalformedBody);
+            }
+            headers.append(key, value.trim());
+        }
+    }
+    // Data up to the next delimiter, `0` once it is reached.
+    fn read_data(&mut self, out: &mut [u8]) -> Result<usize, HttpError> {
+        if self.state != State::Data {
+            return Ok(0);
+        }
+        loop {
+            let available = &self.buf[self.pos..];
+            let safe = match find(available, &self.delimiter) {
+                Some(0) => {
+                    self.pos += self.delimiter.len();
+                    self.state = State::Delimiter;
+                    return Ok(0);
+                }
+                Some(i) => i,
+                // The tail could be the start of a delimiter cut in two.
+                None => available.len().saturating_sub(self.delimiter.len() - 1),
+            };
+            if safe > 0 {
+                let n = safe.min(out.len());
+                out[..n].copy_from_slice(&available[..n]);
+                self.pos += n;
+                return Ok(n);
+            }
+            if self.eof {
+                return Err(HttpError::MalformedBody);
+            }
+            self.fill()?;
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+// Ex: form-data; name="avatar"; filename="me.png"
+fn parse_disposition(value: &str) -> Option<(String, Option<String>)> {
+    let mut params = header::params(value);
+    if !params.next()?.0.eq_ignore_ascii_case("form-data") {
+        return None;
+    }
+    let (mut name, mut filename, mut filename_ext) = (None, None, None);
+    for (key, value) in params {
+        match key.to_ascii_lowercase().as_str() {
+            "name" => name = Some(value),
+            "filename" => filename = Some(value),
+            // RFC 5987, Ex: filename*=UTF-8''na%C3%AFve.txt
+            "filename*" => {
+                filename_ext = value
+                    .split_once("''")
+                    .filter(|(charset, _)| charset.eq_ignore_ascii_case("utf-8"))
+                    .map(|(_, v)| url::decode(v, false))
+            }
+            _ => {}
+        }
+    }
+    Some((name?, filename_ext.or(filename)))
+}
+
+// One part of a multipart body, reading it reads the data of the part.
+pub struct Part<'m, 'a, R> {
+    multipart: &'m mut Multipart<'a, R>,
+    headers: HeaderMap,
+    name: String,
+    filename: Option<String>,
+    read: usize,
+}
+
+impl<R: io::BufRead> Part<'_, '_, R> {
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    // Set for file uploads, as sent by the client. Never use it as a path.
+    #[inline]
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.get(header::CONTENT_TYPE)
+    }
+    #[inline]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+    pub fn bytes(&mut self) -> Result<Vec<u8>, HttpError> {
+        let mut buf = Vec::new();
+        self.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+    pub fn text(&mut self) -> Result<String, HttpError> {
+        String::from_utf8(self.bytes()?).map_err(|_| HttpError::MalformedBody)
+    }
+    // Reads the whole part, keeping it in memory while it is small and
+    // moving it to a temporary file once it grows past the memory limit.
+    pub fn spool(&mut self) -> Result<Spooled, HttpError> {
+        let memory = self.multipart.limits.part_memory;
+        let mut buf = Vec::new();
+        Read::take(&mut *self, memory as u64 + 1).read_to_end(&mut buf)?;
+        if buf.len() <= memory {
+            return Ok(Spooled::Memory(io::Cursor::new(buf)));
+        }
+        let mut file = TempFile::new(self.multipart.limits.upload_dir.as_deref())?;
+        file.file.write_all(&buf)?;
+        io::copy(self, &mut file.file)?;
+        file.file.flush()?;
+        file.file.sync_data()?;
+        io::Seek::rewind(&mut file.file)?;
+        Ok(Spooled::File(file))
+    }
+}
+
+impl<R: io::BufRead> Read for Part<'_, '_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.multipart.read_data(buf)?;
+        self.read += n;
+        if self.read > self.multipart.limits.part_size {
+            return Err(HttpError::PartTooLarge.into());
+        }
+        Ok(n)
+    }
+}
+
+// The data of a part, read into memory or a temporary file.
+#[derive(Debug)]
+pub enum Spooled {
+    Memory(io::Cursor<Vec<u8>>),
+    File(TempFile),
+}
+
+impl Spooled {
+    pub fn len(&self) -> u64 {
+        match self {
+            Self::Memory(data) => data.get_ref().len() as u64,
+            Self::File(file) => file.file.metadata().map_or(0, |m| m.len()),
+        }
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // Moves the data to `path`, replacing what is there.
+    pub fn persist<P: AsRef<Path>>(self, path: P) -> io::Result<()> {
+        match self {
+            Self::Memory(data) => fs::write(path, data.into_inner()),
+            Self::File(file) => file.persist(path),
+        }
+    }
+}
+
+impl Read for Spooled {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Memory(data) => data.read(buf),
+            Self::File(file) => file.file.read(buf),
+        }
+    }
+}
+
+// Removed when dropped unless persisted.
+#[derive(Debug)]
+pub struct TempFile {
+    file: fs::File,
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn new(dir: Option<&Path>) -> io::Result<Self> {
+        let mut name = [0u8; 16];
+        crypto::random_bytes(&mut name);
+        let name = format!("web-server-upload-{}", crypto::base64_encode(&name));
+        let path = match dir {
+            Some(dir) => dir.join(name),
+            None => std::env::temp_dir().join(name),
+        };
+        let mut options = fs::File::options();
+        options.read(true).write(true).create_new(true);
+        // Uploads are nobody else's business, even in a shared /tmp.
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(&path)?;
+        Ok(Self { file, path })
+    }
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn persist<P: AsRef<Path>>(self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        // Renaming fails across file systems, copy there instead.
+        if fs::rename(&self.path, path).is_err() {
+            fs::copy(&self.path, path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
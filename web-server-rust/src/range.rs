@@ -0,0 +1,128 @@
+use std::{ops::Range, time::SystemTime};
+
+use crate::date;
+
+// More ranges than this, once overlapping ones are merged, are answered
+// with the whole file. Many tiny ranges cost more than they are worth.
+const MAX_RANGES: usize = 32;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Ranges {
+    // No usable `Range` header, the whole file is sent.
+    Full,
+    Unsatisfiable,
+    Partial(Vec<Range<u64>>),
+}
+
+// Ex: bytes=0-499, -500, 9500-
+pub(crate) fn parse(value: &str, len: u64) -> Ranges {
+    let Some((unit, specs)) = value.split_once('=') else {
+        return Ranges::Full;
+    };
+    // Unknown units and malformed headers are ignored, not refused.
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return Ranges::Full;
+    }
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some((first, last)) = spec.split_once('-') else {
+            return Ranges::Full;
+        };
+        let number = |s: &str| {
+            if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            s.parse::<u64>().ok()
+        };
+        let range = match (first.trim(), last.trim()) {
+            // The last `n` bytes.
+            ("", n) => match number(n) {
+                Some(0) => None,
+                Some(n) => Some(len.saturating_sub(n)..len),
+                None => return Ranges::Full,
+            },
+            (first, "") => match number(first) {
+                Some(first) => Some(first..len),
+                None => return Ranges::Full,
+            },
+            (first, last) => match (number(first), number(last)) {
+                (Some(first), Some(last)) if first <= last => {
+                    Some(first..last.saturating_add(1).min(len))
+                }
+                _ => return Ranges::Full,
+            },
+        };
+        if let Some(range) = range.filter(|r| r.start < len) {
+            ranges.push(range);
+        }
+    }
+    if ranges.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+    // Overlapping ranges would send the same bytes more than once.
+    // Ex: 0-99, 50-149, 150-199 -> 0-199
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    if merged.len() > MAX_RANGES {
+        return Ranges::Full;
+    }
+    Ranges::Partial(merged)
+}
+
+// `If-Range` holds the validator the client's partial copy came with, the
+// range is only sent while it still matches. Weak tags never match.
+pub(crate) fn if_range_matches(value: &str, etag: &str, modified: Option<SystemTime>) -> bool {
+    let value = value.trim();
+    if value.starts_with('"') {
+        return value == etag;
+    }
+    match (date::parse_http_date(value), modified) {
+        (Some(date), Some(modified)) => date == modified,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ranges, parse};
+
+    #[test]
+    fn ranges() {
+        let partial =
+            |ranges: &[(u64, u64)]| Ranges::Partial(ranges.iter().map(|r| r.0..r.1).collect());
+        assert_eq!(parse("bytes=0-499", 1000), partial(&[(0, 500)]));
+        assert_eq!(
+            parse("bytes=-100, 900-, 10-10", 1000),
+            partial(&[(10, 11), (900, 1000)])
+        );
+        assert_eq!(
+            parse("bytes=100-199, 0-99, 150-249, 300-399", 1000),
+            partial(&[(0, 250), (300, 400)])
+        );
+        assert_eq!(parse("bytes=500-5000", 1000), partial(&[(500, 1000)]));
+        assert_eq!(parse("bytes=-5000", 1000), partial(&[(0, 1000)]));
+        assert_eq!(parse("bytes=1000-", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=0-", 0), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=5-1", 1000), Ranges::Full);
+        assert_eq!(parse("bytes=a-b", 1000), Ranges::Full);
+        assert_eq!(parse("bytes=+1-2", 1000), Ranges::Full);
+        assert_eq!(parse("items=0-1", 1000), Ranges::Full);
+        // Only distinct ranges count towards the limit.
+        assert_eq!(
+            parse(&format!("bytes={}", "0-0,".repeat(33)), 1000),
+            partial(&[(0, 1)])
+        );
+        let many: Vec<String> = (0..33).map(|i| format!("{}-{}", i * 2, i * 2)).collect();
+        assert_eq!(
+            parse(&format!("bytes={}", many.join(",")), 1000),
+            Ranges::Full
+        );
+    }
+}
@@ -1,23 +1,41 @@
 use std::{
-    collections::HashMap,
-    io,
+    io::{self, Read},
     ops::Deref,
     sync::{Arc, Mutex},
 };
 
-use crate::method::Method;
+use crate::{
+    app::Params,
+    body::{BodyReader, BodyState, Limits},
+    cookie,
+    error::HttpError,
+    header::{self, HeaderMap},
+    json::Json,
+    method::Method,
+    mime::MimeType,
+    multipart::Multipart,
+    session::Session,
+    url::{self, UrlEncoded},
+};
 
 #[allow(unused)]
 pub struct HttpRequest<R> {
     pub(crate) method: Method,
     pub(crate) path: String,
+    pub(crate) query: UrlEncoded,
+    query_string: String,
     pub(crate) version: String,
-    pub(crate) header: HashMap<String, String>,
+    pub(crate) header: HeaderMap,
+    pub(crate) params: Params,
+    // Set by the session middleware.
+    pub(crate) session: Option<Session>,
     reader: Arc<Mutex<R>>,
+    pub(crate) body: Arc<Mutex<BodyState>>,
+    pub(crate) limits: Limits,
 }
 
 impl<R> Deref for HttpRequest<R> {
-    type Target = HashMap<String, String>;
+    type Target = HeaderMap;
     fn deref(&self) -> &Self::Target {
         &self.header
     }
@@ -33,25 +51,178 @@ impl<R> HttpRequest<R> {
 impl<R: io::Read> HttpRequest<R> {
     pub fn new(
         method: Method,
-        path: String,
+        target: String,
         version: String,
-        header: HashMap<String, String>,
+        header: HeaderMap,
         r: Arc<Mutex<R>>,
     ) -> Self {
+        // Ex: /search?q=rust+web&tag=a&tag=b
+        let (path, query_string) = match target.split_once('?') {
+            Some((path, query)) => (path, query),
+            None => (target.as_str(), ""),
+        };
+        // The server answers `400` before getting here when the path doesn't
+        // decode, only a request built by hand can keep its raw path.
+        let path = url::decode_path(path).unwrap_or_else(|_| path.to_owned());
+        let query = UrlEncoded::parse(query_string);
+        let query_string = query_string.to_owned();
+        let body = BodyState::new(
+            header.get(header::CONTENT_LENGTH),
+            header.get(header::TRANSFER_ENCODING),
+        );
         Self {
             method,
             path,
+            query,
+            query_string,
             version,
             header,
+            params: Params::default(),
+            session: None,
             reader: r,
+            body: Arc::new(Mutex::new(body)),
+            limits: Limits::default(),
         }
     }
     #[inline]
-    pub fn insert_header(&mut self, key: String, value: String) {
+    pub fn insert_header<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
         self.header.insert(key, value);
     }
     #[inline]
-    pub fn get_headers(&self) -> &HashMap<String, String> {
+    pub fn get_headers(&self) -> &HeaderMap {
         &self.header
     }
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    #[inline]
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query.get(name)
+    }
+    #[inline]
+    pub fn query_all(&self, name: &str) -> Vec<&str> {
+        self.query.get_all(name)
+    }
+    #[inline]
+    pub fn query_pairs(&self) -> &UrlEncoded {
+        &self.query
+    }
+    #[inline]
+    pub fn query_string(&self) -> &str {
+        &self.query_string
+    }
+    #[inline]
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+    #[inline]
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name)
+    }
+    // `None` without a valid `Content-Type`.
+    // Ex: req.content_type() == Some(MimeType::ApplicationJson)
+    pub fn content_type(&self) -> Option<MimeType> {
+        self.header.get(header::CONTENT_TYPE)?.parse().ok()
+    }
+    // Compares the `Content-Type` without its parameters.
+    // Ex: req.is_content_type("application/json")
+    pub fn is_content_type(&self, media_type: &str) -> bool {
+        self.header.get(header::CONTENT_TYPE).is_some_and(|value| {
+            value
+                .split(';')
+                .next()
+                .unwrap()
+                .trim()
+                .eq_ignore_ascii_case(media_type)
+        })
+    }
+    // `None` unless the session middleware runs first.
+    #[inline]
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+    // Every cookie sent by the client, in order.
+    pub fn cookies(&self) -> Vec<(&str, &str)> {
+        self.header
+            .get_all(header::COOKIE)
+            .flat_map(cookie::parse)
+            .collect()
+    }
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.header
+            .get_all(header::COOKIE)
+            .flat_map(cookie::parse)
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+    #[inline]
+    pub fn set_body_limit(&self, limit: usize) {
+        self.body.lock().unwrap().set_limit(limit);
+    }
+}
+
+impl<R: io::BufRead> HttpRequest<R> {
+    pub fn body_reader(&self) -> BodyReader<'_, R> {
+        BodyReader {
+            reader: &self.reader,
+            state: &self.body,
+        }
+    }
+    pub fn body_bytes(&self) -> Result<Vec<u8>, HttpError> {
+        let mut buf = Vec::new();
+        self.body_reader().read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+    pub fn discard_body(&self) -> Result<(), HttpError> {
+        io::copy(&mut self.body_reader(), &mut io::sink())?;
+        Ok(())
+    }
+    pub fn body_string(&self) -> Result<String, HttpError> {
+        String::from_utf8(self.body_bytes()?).map_err(|_| HttpError::MalformedBody)
+    }
+    // Fields of an `application/x-www-form-urlencoded` body.
+    pub fn form(&self) -> Result<UrlEncoded, HttpError> {
+        if !self.is_content_type("application/x-www-form-urlencoded") {
+            return Err(HttpError::UnsupportedMediaType);
+        }
+        let limit = self.limits.form_size;
+        let mut body = Vec::new();
+        io::Read::take(self.body_reader(), limit as u64 + 1).read_to_end(&mut body)?;
+        if body.len() > limit {
+            return Err(HttpError::ContentTooLarge);
+        }
+        let body = String::from_utf8(body).map_err(|_| HttpError::MalformedBody)?;
+        UrlEncoded::parse_limited(&body, self.limits.form_fields).ok_or(HttpError::TooManyFields)
+    }
+    // Streams a `multipart/form-data` body, see `Multipart`.
+    pub fn multipart(&self) -> Result<Multipart<'_, R>, HttpError> {
+        if !self.is_content_type("multipart/form-data") {
+            return Err(HttpError::UnsupportedMediaType);
+        }
+        let boundary = self
+            .header
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| {
+                header::params(value).find(|(key, _)| key.eq_ignore_ascii_case("boundary"))
+            })
+            .map(|(_, boundary)| boundary)
+            .filter(|boundary| (1..=70).contains(&boundary.len()))
+            .ok_or(HttpError::BadRequest)?;
+        self.set_body_limit(self.limits.multipart_size);
+        Ok(Multipart::new(
+            self.body_reader(),
+            &boundary,
+            self.limits.clone(),
+        ))
+    }
+    // Fails with `InvalidJson`, a `400`, on anything but a JSON document.
+    pub fn json(&self) -> Result<Json, HttpError> {
+        let body = String::from_utf8(self.body_bytes()?).map_err(|_| HttpError::InvalidJson)?;
+        Json::parse(&body)
+    }
+    #[cfg(feature = "serde")]
+    pub fn json_as<T: serde::de::DeserializeOwned>(&self) -> Result<T, HttpError> {
+        serde_json::from_slice(&self.body_bytes()?).map_err(|_| HttpError::InvalidJson)
+    }
 }
@@ -1,28 +1,86 @@
 use std::{
-    collections::HashMap,
-    fmt, fs,
-    io::{self, BufReader},
+    fmt::{self, Write as _},
+    fs,
+    io::{self, BufReader, Read, Seek, SeekFrom},
     path::Path,
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 
-use crate::{MimeType, Status};
+use crate::{
+    MimeType, Status,
+    conditional::{self, Precondition},
+    cookie::Cookie,
+    crypto, date,
+    error::HttpError,
+    header::{self, HeaderMap},
+    json::Json,
+    method::Method,
+    range::{self, Ranges},
+};
+
+// Copied from the request for `send_file`.
+pub(crate) const REQUEST_HEADERS: [&str; 6] = [
+    header::RANGE,
+    header::IF_RANGE,
+    header::IF_MATCH,
+    header::IF_NONE_MATCH,
+    header::IF_MODIFIED_SINCE,
+    header::IF_UNMODIFIED_SINCE,
+];
+
+// Shared with the server so it can decide whether the connection may be
+// reused once the handler returns.
+#[derive(Debug, Default)]
+pub(crate) struct ResponseState {
+    pub(crate) keep_alive: AtomicBool,
+    // HTTP/1.0 clients only keep the connection open when told so.
+    pub(crate) explicit: bool,
+    pub(crate) sent: AtomicBool,
+}
+
+impl ResponseState {
+    pub(crate) fn new(keep_alive: bool, explicit: bool) -> Self {
+        Self {
+            keep_alive: AtomicBool::new(keep_alive),
+            explicit,
+            sent: AtomicBool::new(false),
+        }
+    }
+}
 
 #[allow(unused)]
 pub struct HttpResponse<W> {
     status: Mutex<Status>,
-    content_type: Mutex<MimeType>,
-    header: Mutex<HashMap<String, String>>,
+    // `None` until set, `send_file` then goes by the file extension.
+    content_type: Mutex<Option<MimeType>>,
+    // Shared with the request session so it can set its cookie.
+    pub(crate) header: Arc<Mutex<HeaderMap>>,
     writer: Arc<Mutex<W>>,
+    pub(crate) connection: Arc<ResponseState>,
+    // Answering a `HEAD` request, only the head is written.
+    pub(crate) head: bool,
+    pub(crate) method: Method,
+    // The `REQUEST_HEADERS` sent with the request.
+    pub(crate) request_header: HeaderMap,
+    // Give dynamic bodies a weak `ETag`.
+    pub(crate) etag: bool,
 }
 
 impl<W> From<Arc<Mutex<W>>> for HttpResponse<W> {
     fn from(value: Arc<Mutex<W>>) -> Self {
         Self {
             status: Mutex::new(Status::default()),
-            content_type: Mutex::new(MimeType::default()),
-            header: Mutex::new(HashMap::new()),
+            content_type: Mutex::new(None),
+            header: Arc::default(),
             writer: value,
+            connection: Arc::default(),
+            head: false,
+            method: Method::Get,
+            request_header: HeaderMap::new(),
+            etag: true,
         }
     }
 }
@@ -31,9 +89,14 @@ impl<W: io::Write> HttpResponse<W> {
     pub fn new(w: Arc<Mutex<W>>) -> Self {
         Self {
             status: Mutex::new(Status::default()),
-            content_type: Mutex::new(MimeType::default()),
-            header: Mutex::new(HashMap::new()),
+            content_type: Mutex::new(None),
+            header: Arc::default(),
             writer: w,
+            connection: Arc::default(),
+            head: false,
+            method: Method::Get,
+            request_header: HeaderMap::new(),
+            etag: true,
         }
     }
     #[allow(unused)]
@@ -45,41 +108,636 @@ impl<W: io::Write> HttpResponse<W> {
         self
     }
     pub fn content_type(&self, t: MimeType) -> &Self {
-        *self.content_type.lock().unwrap() = t;
+        *self.content_type.lock().unwrap() = Some(t);
         self
     }
-    pub fn insert_header(&self, key: String, value: String) -> &Self {
+    // Replaces any previous value. Fails on names that are not tokens and
+    // on values holding CR or LF.
+    pub fn insert_header<K, V>(&self, key: K, value: V) -> Result<&Self, HttpError>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let (key, value) = (key.into(), value.into());
+        header::validate(&key, &value)?;
         self.header.lock().unwrap().insert(key, value);
-        self
+        Ok(self)
+    }
+    // Adds a value next to the previous ones. Ex: Set-Cookie
+    pub fn append_header<K, V>(&self, key: K, value: V) -> Result<&Self, HttpError>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let (key, value) = (key.into(), value.into());
+        header::validate(&key, &value)?;
+        self.header.lock().unwrap().append(key, value);
+        Ok(self)
+    }
+    #[inline]
+    pub fn get_header(&self, key: &str) -> Option<String> {
+        self.header.lock().unwrap().get(key).map(str::to_owned)
+    }
+    #[inline]
+    pub fn remove_header(&self, key: &str) -> Option<String> {
+        self.header.lock().unwrap().remove(key)
     }
-    fn send_res_head(&self, len: usize) -> io::Result<()> {
+    pub fn set_cookie(&self, cookie: Cookie) -> Result<&Self, HttpError> {
+        cookie.validate()?;
+        self.append_header(header::SET_COOKIE, cookie.to_string())
+    }
+    // Only removes cookies set without `Path` or `Domain`, send a
+    // `Cookie::removal` with the same ones otherwise.
+    #[inline]
+    pub fn remove_cookie(&self, name: &str) -> Result<&Self, HttpError> {
+        self.set_cookie(Cookie::removal(name))
+    }
+    // `None` streams the body with chunked transfer coding.
+    fn send_res_head(&self, len: Option<u64>) -> io::Result<()> {
         let http_version = "HTTP/1.1";
-        let mut v = self.writer.lock().unwrap();
+        let mut head = String::new();
         // Ex: HTTP/1.1 200 OK
-        writeln!(v, "{} {}\r", http_version, self.status.lock().unwrap())?;
-        writeln!(v, "Content-Length: {}\r", len)?;
-        writeln!(v, "Content-Type: {}\r", self.content_type.lock().unwrap())?;
-        for (key, value) in self.header.lock().unwrap().iter() {
-            writeln!(v, "{}: {}\r", key, value)?;
+        write!(head, "{} {}\r\n", http_version, self.status.lock().unwrap()).unwrap();
+        let mut keep_alive = self.connection.keep_alive.load(Ordering::Relaxed);
+        match len {
+            // Never has a body, a length would describe the one not sent.
+            Some(_) if !self.has_body() => {}
+            Some(len) => write!(head, "Content-Length: {}\r\n", len).unwrap(),
+            None if self.chunked() => head.push_str("Transfer-Encoding: chunked\r\n"),
+            // HTTP/1.0 has no chunked coding, closing the connection ends the body.
+            None => keep_alive = false,
+        }
+        let header = self.header.lock().unwrap();
+        // A `Content-Type` header set by hand wins over `content_type`.
+        if !header.contains_key(header::CONTENT_TYPE) {
+            let content_type = self.content_type.lock().unwrap();
+            let content_type = content_type.clone().unwrap_or_default().to_string();
+            // `MimeType::Custom` holds anything.
+            header::validate(header::CONTENT_TYPE, &content_type)?;
+            write!(head, "Content-Type: {}\r\n", content_type).unwrap();
+        }
+        let connection = header.get(header::CONNECTION);
+        if connection.is_some_and(|value| {
+            value
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case("close"))
+        }) {
+            keep_alive = false;
+        }
+        // A `Connection: keep-alive` set by hand can't keep open a connection
+        // the server is about to close.
+        match connection {
+            _ if !keep_alive => head.push_str("Connection: close\r\n"),
+            Some(value) => write!(head, "Connection: {}\r\n", value).unwrap(),
+            None if self.connection.explicit => head.push_str("Connection: keep-alive\r\n"),
+            None => {}
+        }
+        for (key, value) in header.iter() {
+            // The framing is ours, a stale length would corrupt the stream.
+            if key.eq_ignore_ascii_case(header::CONTENT_LENGTH)
+                || key.eq_ignore_ascii_case(header::TRANSFER_ENCODING)
+                || key.eq_ignore_ascii_case(header::CONNECTION)
+            {
+                continue;
+            }
+            write!(head, "{}: {}\r\n", key, value).unwrap();
         }
-        writeln!(v, "\r")?;
+        head.push_str("\r\n");
+        self.writer.lock().unwrap().write_all(head.as_bytes())?;
+        self.connection
+            .keep_alive
+            .store(keep_alive, Ordering::Relaxed);
+        self.connection.sent.store(true, Ordering::Relaxed);
         Ok(())
     }
     pub fn send<T: fmt::Display>(self, value: T) -> io::Result<()> {
-        let val = value.to_string();
-        self.send_res_head(val.len())?;
-        writeln!(self.writer.lock().unwrap(), "{}", val)?;
-        Ok(())
+        self.send_bytes(value.to_string().as_bytes())
+    }
+    // Only sets the content type, the status is whatever `status` set and
+    // `200 OK` otherwise. Ex: res.status(Status::Created); res.json(&user)
+    pub fn json(self, value: &Json) -> io::Result<()> {
+        self.content_type(MimeType::ApplicationJson);
+        self.send_bytes(value.to_string().as_bytes())
+    }
+    // Same as `json`, for any `Serialize` type.
+    #[cfg(feature = "serde")]
+    pub fn json_as<T: serde::Serialize + ?Sized>(self, value: &T) -> io::Result<()> {
+        let body = serde_json::to_vec(value)?;
+        self.content_type(MimeType::ApplicationJson);
+        self.send_bytes(&body)
+    }
+    pub fn send_bytes(self, value: &[u8]) -> io::Result<()> {
+        if self.etag && self.is_cacheable() {
+            let mut header = self.header.lock().unwrap();
+            if !header.contains_key(header::ETAG) {
+                let hash = crypto::sha256(value);
+                let etag = format!("W/\"{}\"", crypto::base64_encode(&hash[..16]));
+                header.insert(header::ETAG, etag);
+            }
+        }
+        if self.answer_preconditions()? {
+            return Ok(());
+        }
+        self.send_res_head(Some(value.len() as u64))?;
+        if self.head {
+            return Ok(());
+        }
+        self.writer.lock().unwrap().write_all(value)
+    }
+    // A `200` to a `GET` or `HEAD`, the only answers preconditions and
+    // validators are about here.
+    fn is_cacheable(&self) -> bool {
+        matches!(self.method, Method::Get | Method::Head)
+            && u32::from(*self.status.lock().unwrap()) == 200
+    }
+    // Evaluates the request's conditional headers against the `ETag` and
+    // `Last-Modified` headers set so far. `true` when it answered with `304`
+    // or `412` and there is nothing left to send.
+    fn answer_preconditions(&self) -> io::Result<bool> {
+        if !self.is_cacheable() {
+            return Ok(false);
+        }
+        let header = self.header.lock().unwrap();
+        let etag = header.get(header::ETAG).map(str::to_owned);
+        let modified = header
+            .get(header::LAST_MODIFIED)
+            .and_then(date::parse_http_date);
+        drop(header);
+        let status = match conditional::evaluate(&self.request_header, etag.as_deref(), modified) {
+            Precondition::Pass => return Ok(false),
+            Precondition::NotModified => Status::NotModified,
+            Precondition::Failed => Status::PreconditionFailed,
+        };
+        self.status(status);
+        self.send_res_head(Some(0))?;
+        Ok(true)
+    }
+    #[inline]
+    fn chunked(&self) -> bool {
+        !self.connection.explicit
+    }
+    // `204 No Content` and `304 Not Modified` never carry a body.
+    fn has_body(&self) -> bool {
+        !matches!(u32::from(*self.status.lock().unwrap()), 204 | 304)
+    }
+    // The body is written as it is produced, without knowing its length.
+    // Ex: let mut w = res.stream()?; writeln!(w, "id,name")?; w.finish()?;
+    pub fn stream(self) -> io::Result<ChunkedWriter<W>> {
+        // Without a body there is nothing to frame, what is written is dropped.
+        let has_body = self.has_body();
+        self.send_res_head(if has_body { None } else { Some(0) })?;
+        Ok(ChunkedWriter {
+            writer: self.writer.clone(),
+            connection: self.connection.clone(),
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            trailers: Vec::new(),
+            chunked: self.chunked(),
+            head: self.head || !has_body,
+            finished: false,
+        })
     }
+    // The content type follows the extension unless set before. Comes with
+    // a strong `ETag` and `Last-Modified`, conditional requests get `304` or
+    // `412` and `Range` requests the parts asked for.
     pub fn send_file<P: AsRef<Path>>(self, path: P) -> io::Result<()> {
-        let file = fs::File::open(path)?;
-        let file_len = file.metadata()?.len();
-        self.send_res_head(file_len as usize)?;
-        let mut file_reader = BufReader::new(file);
+        let path = path.as_ref();
+        self.content_type
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| MimeType::from_path(path));
+        let mut file = fs::File::open(path)?;
+        let meta = file.metadata()?;
+        let file_len = meta.len();
+        {
+            let mut header = self.header.lock().unwrap();
+            header.insert(header::ACCEPT_RANGES, "bytes");
+            if !header.contains_key(header::ETAG) {
+                header.insert(header::ETAG, file_etag(&meta));
+            }
+            if !header.contains_key(header::LAST_MODIFIED)
+                && let Ok(modified) = meta.modified()
+            {
+                header.insert(header::LAST_MODIFIED, date::http_date(modified));
+            }
+        }
+        if self.answer_preconditions()? {
+            return Ok(());
+        }
+        let ranges = match self.request_header.get(header::RANGE) {
+            Some(value) if self.wants_range() => range::parse(value, file_len),
+            _ => Ranges::Full,
+        };
+        match ranges {
+            Ranges::Full => {
+                self.send_res_head(Some(file_len))?;
+                self.write_file(&mut file, 0..file_len)
+            }
+            Ranges::Unsatisfiable => {
+                self.status(Status::RangeNotSatisfiable);
+                self.header
+                    .lock()
+                    .unwrap()
+                    .insert(header::CONTENT_RANGE, format!("bytes */{}", file_len));
+                self.send_bytes(&[])
+            }
+            Ranges::Partial(ranges) if ranges.len() == 1 => {
+                let range = ranges[0].clone();
+                self.status(Status::PartialContent);
+                self.header.lock().unwrap().insert(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", range.start, range.end - 1, file_len),
+                );
+                self.send_res_head(Some(range.end - range.start))?;
+                self.write_file(&mut file, range)
+            }
+            Ranges::Partial(ranges) => self.send_byteranges(&mut file, file_len, ranges),
+        }
+    }
+    // Ranges only make sense for a `GET` answered with the file itself,
+    // and while the client's copy is still current.
+    fn wants_range(&self) -> bool {
+        if self.method != Method::Get || u32::from(*self.status.lock().unwrap()) != 200 {
+            return false;
+        }
+        let Some(value) = self.request_header.get(header::IF_RANGE) else {
+            return true;
+        };
+        let header = self.header.lock().unwrap();
+        let modified = header
+            .get(header::LAST_MODIFIED)
+            .and_then(date::parse_http_date);
+        range::if_range_matches(value, header.get(header::ETAG).unwrap_or(""), modified)
+    }
+    // Ex:
+    //     --boundary
+    //     Content-Type: video/mp4
+    //     Content-Range: bytes 0-99/1000
+    //
+    //     <100 bytes>
+    fn send_byteranges(
+        self,
+        file: &mut fs::File,
+        file_len: u64,
+        ranges: Vec<std::ops::Range<u64>>,
+    ) -> io::Result<()> {
+        let mut boundary = [0u8; 18];
+        crypto::random_bytes(&mut boundary);
+        let boundary = crypto::base64_encode(&boundary);
+        let content_type = match self.header.lock().unwrap().get(header::CONTENT_TYPE) {
+            Some(value) => value.to_owned(),
+            None => self
+                .content_type
+                .lock()
+                .unwrap()
+                .clone()
+                .unwrap_or_default()
+                .to_string(),
+        };
+        let heads: Vec<String> = ranges
+            .iter()
+            .map(|range| {
+                format!(
+                    "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                    boundary,
+                    content_type,
+                    range.start,
+                    range.end - 1,
+                    file_len
+                )
+            })
+            .collect();
+        let end = format!("\r\n--{}--\r\n", boundary);
+        let len = heads.iter().map(|h| h.len() as u64).sum::<u64>()
+            + ranges.iter().map(|r| r.end - r.start).sum::<u64>()
+            + end.len() as u64;
+        self.status(Status::PartialContent);
+        self.header.lock().unwrap().insert(
+            header::CONTENT_TYPE,
+            format!("multipart/byteranges; boundary={}", boundary),
+        );
+        self.send_res_head(Some(len))?;
+        if self.head {
+            return Ok(());
+        }
+        for (head, range) in heads.iter().zip(ranges) {
+            self.writer.lock().unwrap().write_all(head.as_bytes())?;
+            self.write_file(file, range)?;
+        }
+        self.writer.lock().unwrap().write_all(end.as_bytes())
+    }
+    fn write_file(&self, file: &mut fs::File, range: std::ops::Range<u64>) -> io::Result<()> {
+        if self.head {
+            return Ok(());
+        }
+        let len = range.end - range.start;
+        file.seek(SeekFrom::Start(range.start))?;
+        let mut file_reader = BufReader::new(file).take(len);
         let mut v = self.writer.lock().unwrap();
-        io::copy(&mut file_reader, &mut *v)?;
+        let copied = io::copy(&mut file_reader, &mut *v)?;
+        if copied < len {
+            // The file shrank, the promised length can't be honored.
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
         Ok(())
     }
 }
 
-impl<W: io::Write> HttpResponse<W> {}
+// Strong, changes with the size or the modification time.
+// Ex: "1f4-65a8e3c0-0"
+fn file_etag(meta: &fs::Metadata) -> String {
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!(
+        "\"{:x}-{:x}-{:x}\"",
+        meta.len(),
+        modified.as_secs(),
+        modified.subsec_nanos()
+    )
+}
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+pub struct ChunkedWriter<W: io::Write> {
+    writer: Arc<Mutex<W>>,
+    connection: Arc<ResponseState>,
+    buf: Vec<u8>,
+    trailers: Vec<(String, String)>,
+    chunked: bool,
+    head: bool,
+    finished: bool,
+}
+
+impl<W: io::Write> ChunkedWriter<W> {
+    // Sent after the last chunk, ignored for HTTP/1.0 clients.
+    pub fn trailer<K, V>(&mut self, key: K, value: V) -> Result<&mut Self, HttpError>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let (key, value) = (key.into(), value.into());
+        header::validate(&key, &value)?;
+        self.trailers.push((key, value));
+        Ok(self)
+    }
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() || self.head {
+            self.buf.clear();
+            return Ok(());
+        }
+        let mut w = self.writer.lock().unwrap();
+        if self.chunked {
+            write!(w, "{:X}\r\n", self.buf.len())?;
+            w.write_all(&self.buf)?;
+            w.write_all(b"\r\n")?;
+        } else {
+            w.write_all(&self.buf)?;
+        }
+        self.buf.clear();
+        Ok(())
+    }
+    // Writes what is left, the last chunk and the trailers. Dropping the
+    // writer does the same but can't report an error.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.end()
+    }
+    fn end(&mut self) -> io::Result<()> {
+        self.finished = true;
+        self.write_chunk()?;
+        let mut w = self.writer.lock().unwrap();
+        if self.chunked && !self.head {
+            w.write_all(b"0\r\n")?;
+            for (key, value) in &self.trailers {
+                write!(w, "{}: {}\r\n", key, value)?;
+            }
+            w.write_all(b"\r\n")?;
+        }
+        w.flush()
+    }
+}
+
+impl<W: io::Write> io::Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.write_chunk()?;
+        }
+        Ok(buf.len())
+    }
+    // Pushes what was written so far to the client.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_chunk()?;
+        self.writer.lock().unwrap().flush()
+    }
+}
+
+impl<W: io::Write> Drop for ChunkedWriter<W> {
+    // The connection can't be reused if the body couldn't be ended.
+    fn drop(&mut self) {
+        if !self.finished && self.end().is_err() {
+            self.connection.keep_alive.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        sync::{Arc, Mutex, atomic::Ordering},
+    };
+
+    use super::{HttpResponse, ResponseState};
+    use crate::Status;
+
+    struct Parsed {
+        status: u32,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Parsed {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    // Rejects anything a strict HTTP/1.1 client would: bare LF, obs-fold,
+    // whitespace around field names and bodies not matching Content-Length.
+    fn parse_strict(raw: &[u8]) -> Parsed {
+        let end = raw
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .expect("missing end of header section");
+        let head = std::str::from_utf8(&raw[..end]).expect("head is not utf-8");
+        assert!(
+            !head.replace("\r\n", "").contains(['\r', '\n']),
+            "bare CR or LF"
+        );
+        let mut lines = head.split("\r\n");
+        let status_line = lines.next().unwrap();
+        let mut parts = status_line.splitn(3, ' ');
+        assert_eq!(parts.next(), Some("HTTP/1.1"));
+        let status = parts.next().unwrap();
+        assert_eq!(status.len(), 3);
+        let status = status.parse().unwrap();
+        assert!(!parts.next().unwrap().is_empty(), "missing reason phrase");
+        let headers: Vec<_> = lines
+            .map(|line| {
+                assert!(!line.starts_with([' ', '\t']), "obsolete line folding");
+                let (k, v) = line.split_once(':').expect("header without colon");
+                assert!(!k.is_empty() && !k.ends_with([' ', '\t']), "bad field name");
+                (k.to_owned(), v.trim().to_owned())
+            })
+            .collect();
+        let lengths: Vec<_> = headers
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case("Content-Length"))
+            .collect();
+        assert_eq!(lengths.len(), 1, "exactly one Content-Length expected");
+        let len: usize = lengths[0].1.parse().unwrap();
+        let body = raw[end + 4..].to_vec();
+        assert_eq!(body.len(), len, "body does not match Content-Length");
+        Parsed {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    fn response() -> (HttpResponse<Vec<u8>>, Arc<Mutex<Vec<u8>>>) {
+        let out = Arc::new(Mutex::new(Vec::new()));
+        (HttpResponse::new(out.clone()), out)
+    }
+
+    #[test]
+    fn send_writes_exact_body() {
+        let (res, out) = response();
+        res.status(Status::Created);
+        res.send("Home Page\n").unwrap();
+        let parsed = parse_strict(&out.lock().unwrap());
+        assert_eq!(parsed.status, 201);
+        assert_eq!(parsed.body, b"Home Page\n");
+        assert_eq!(
+            parsed.header("Content-Type"),
+            Some("text/html; charset=utf-8")
+        );
+    }
+
+    #[test]
+    fn send_empty_body() {
+        let (res, out) = response();
+        res.send("").unwrap();
+        let parsed = parse_strict(&out.lock().unwrap());
+        assert!(parsed.body.is_empty());
+        assert_eq!(parsed.header("Content-Length"), Some("0"));
+    }
+
+    #[test]
+    fn send_bytes_is_byte_exact() {
+        let (res, out) = response();
+        let body = [0u8, 159, 146, 150, b'\r', b'\n', b'\n', 255];
+        res.send_bytes(&body).unwrap();
+        assert_eq!(parse_strict(&out.lock().unwrap()).body, body);
+    }
+
+    #[test]
+    fn user_content_length_is_ignored() {
+        let (res, out) = response();
+        res.insert_header("content-length", "100").unwrap();
+        res.insert_header("X-Test", "yes").unwrap();
+        res.send("abc").unwrap();
+        let parsed = parse_strict(&out.lock().unwrap());
+        assert_eq!(parsed.body, b"abc");
+        assert_eq!(parsed.header("X-Test"), Some("yes"));
+    }
+
+    #[test]
+    fn send_file_matches_length() {
+        let path = std::env::temp_dir().join(format!("web-server-test-{}", std::process::id()));
+        fs::write(&path, b"line 1\nline 2\n").unwrap();
+        let (res, out) = response();
+        res.send_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(parse_strict(&out.lock().unwrap()).body, b"line 1\nline 2\n");
+    }
+
+    #[test]
+    fn head_response_has_no_body() {
+        let (mut res, out) = response();
+        res.head = true;
+        res.send("hidden").unwrap();
+        let raw = out.lock().unwrap();
+        assert!(raw.ends_with(b"\r\n\r\n"));
+        assert!(
+            std::str::from_utf8(&raw)
+                .unwrap()
+                .contains("Content-Length: 6\r\n")
+        );
+    }
+
+    #[test]
+    fn stream_writes_chunks_and_trailers() {
+        use std::io::Write;
+
+        let (res, out) = response();
+        let mut w = res.stream().unwrap();
+        w.write_all(b"hello ").unwrap();
+        w.flush().unwrap();
+        w.write_all(b"world").unwrap();
+        w.trailer("X-Done", "1").unwrap();
+        assert!(w.trailer("X-Bad", "a\r\nb").is_err());
+        w.finish().unwrap();
+        let raw = String::from_utf8(out.lock().unwrap().clone()).unwrap();
+        let (head, body) = raw.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("\r\nTransfer-Encoding: chunked"));
+        assert!(!head.contains("Content-Length"));
+        assert_eq!(body, "6\r\nhello \r\n5\r\nworld\r\n0\r\nX-Done: 1\r\n\r\n");
+    }
+
+    #[test]
+    fn stream_dropped_without_finish_is_ended() {
+        use std::io::Write;
+
+        let (mut res, out) = response();
+        res.connection = Arc::new(ResponseState::new(true, false));
+        let connection = res.connection.clone();
+        let mut w = res.stream().unwrap();
+        w.write_all(b"hello").unwrap();
+        w.flush().unwrap();
+        w.write_all(b" world").unwrap();
+        drop(w);
+        let raw = String::from_utf8(out.lock().unwrap().clone()).unwrap();
+        assert!(raw.ends_with("\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n"));
+        assert!(connection.keep_alive.load(Ordering::Relaxed));
+
+        // HTTP/1.0 bodies end with the connection, nothing may be left behind.
+        let (mut res, out) = response();
+        res.connection = Arc::new(ResponseState::new(true, true));
+        let mut w = res.stream().unwrap();
+        w.write_all(b"hello").unwrap();
+        drop(w);
+        assert!(out.lock().unwrap().ends_with(b"\r\n\r\nhello"));
+    }
+
+    #[test]
+    fn stream_without_body_has_no_framing() {
+        use std::io::Write;
+
+        let (res, out) = response();
+        res.status(Status::NoContent);
+        let mut w = res.stream().unwrap();
+        w.write_all(b"ignored").unwrap();
+        w.finish().unwrap();
+        let raw = String::from_utf8(out.lock().unwrap().clone()).unwrap();
+        assert!(raw.starts_with("HTTP/1.1 204 "));
+        assert!(raw.ends_with("\r\n\r\n"));
+        assert!(!raw.contains("Transfer-Encoding"));
+        assert!(!raw.contains("Content-Length"));
+    }
+}
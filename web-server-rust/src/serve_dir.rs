@@ -0,0 +1,161 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    MimeType, Status,
+    error::HttpError,
+    header,
+    request::HttpRequest,
+    response::HttpResponse,
+    server::{Reader, Writer},
+    url,
+};
+
+// Files under a directory, mounted with `App::serve_dir_with`.
+// Ex: app.serve_dir_with("/files", ServeDir::new("./files").listing(true));
+#[derive(Debug, Clone)]
+pub struct ServeDir {
+    root: PathBuf,
+    index: String,
+    listing: bool,
+    dotfiles: bool,
+}
+
+impl ServeDir {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            index: "index.html".to_owned(),
+            listing: false,
+            dotfiles: false,
+        }
+    }
+    // Served for a directory, `index.html` by default.
+    pub fn index<N: Into<String>>(mut self, name: N) -> Self {
+        self.index = name.into();
+        self
+    }
+    // Lists directories without an index file.
+    pub fn listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+        self
+    }
+    // Serve names starting with `.`, like `.env` or `.git`.
+    pub fn dotfiles(mut self, dotfiles: bool) -> Self {
+        self.dotfiles = dotfiles;
+        self
+    }
+    pub(crate) fn serve(
+        &self,
+        req: HttpRequest<Reader>,
+        res: HttpResponse<Writer>,
+    ) -> io::Result<()> {
+        let rel = req.param("path").unwrap_or("");
+        let path = self.resolve(rel).ok_or(HttpError::NotFound)?;
+        if path.is_file() {
+            return res.send_file(&path);
+        }
+        if !path.is_dir() {
+            return Err(HttpError::NotFound.into());
+        }
+        // Relative links in the page must resolve inside the directory.
+        if !req.path().ends_with('/') {
+            let location: Vec<_> = req.path().split('/').map(url::encode).collect();
+            res.status(Status::MovedPermanently);
+            res.insert_header(header::LOCATION, format!("{}/", location.join("/")))?;
+            return res.send("");
+        }
+        if let Some(index) = self.contained(&path.join(&self.index))
+            && index.is_file()
+        {
+            return res.send_file(&index);
+        }
+        if self.listing {
+            let at_root = rel.trim_matches('/').is_empty();
+            let page = self.listing_page(&path, req.path(), at_root)?;
+            res.content_type(MimeType::TextHtml);
+            return res.send(page);
+        }
+        Err(HttpError::NotFound.into())
+    }
+    // The real path of `rel` below the root. `None` for anything that could
+    // leave it: `..`, absolute paths, drive prefixes and symlinks pointing
+    // outside. The request path is already percent-decoded, so encoded
+    // traversal ends up here as plain `..` too.
+    fn resolve(&self, rel: &str) -> Option<PathBuf> {
+        let mut path = self.root.clone();
+        for segment in rel.split('/').filter(|s| !s.is_empty()) {
+            let mut components = Path::new(segment).components();
+            let single = matches!(components.next(), Some(Component::Normal(_)))
+                && components.next().is_none();
+            if !single || segment.contains(['\\', '\0']) || !self.visible(segment) {
+                return None;
+            }
+            path.push(segment);
+        }
+        self.contained(&path)
+    }
+    fn contained(&self, path: &Path) -> Option<PathBuf> {
+        let root = fs::canonicalize(&self.root).ok()?;
+        let path = fs::canonicalize(path).ok()?;
+        path.starts_with(&root).then_some(path)
+    }
+    #[inline]
+    fn visible(&self, name: &str) -> bool {
+        self.dotfiles || !name.starts_with('.')
+    }
+    fn listing_page(&self, dir: &Path, request_path: &str, at_root: bool) -> io::Result<String> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+            if self.visible(&name) {
+                entries.push((name, entry.file_type()?.is_dir()));
+            }
+        }
+        // Directories first, then by name.
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let title = escape_html(&format!("Index of {}", request_path));
+        let mut page = format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n\
+             <body>\n<h1>{0}</h1>\n<ul>\n",
+            title
+        );
+        if !at_root {
+            page.push_str("<li><a href=\"../\">../</a></li>\n");
+        }
+        for (name, is_dir) in entries {
+            let slash = if is_dir { "/" } else { "" };
+            writeln!(
+                page,
+                "<li><a href=\"{}{2}\">{}{2}</a></li>",
+                url::encode(&name),
+                escape_html(&name),
+                slash
+            )
+            .unwrap();
+        }
+        page.push_str("</ul>\n</body>\n</html>\n");
+        Ok(page)
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
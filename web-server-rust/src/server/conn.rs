@@ -0,0 +1,115 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+pub type Closer = Box<dyn Fn(Shutdown) + Send + Sync>;
+pub type Waker = Box<dyn Fn() + Send + Sync>;
+
+/// A bidirectional stream the server can read requests from and write
+/// responses to. Ex: TcpStream, a TLS session, a pair of pipes.
+pub trait Connection: Send {
+    /// Separates the reading and writing halves, so that requests and
+    /// responses can be buffered independently.
+    fn split(self: Box<Self>) -> io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)>;
+    /// Used for the keep-alive timeout. Transports that don't support
+    /// timeouts keep the default, and idle connections never time out.
+    fn set_read_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+    /// Returns a function that closes the connection from another thread,
+    /// used on shutdown. The default does nothing.
+    fn closer(&self) -> io::Result<Closer> {
+        Ok(Box::new(|_| {}))
+    }
+}
+
+/// A source of connections. Ex: TcpListener, a Unix socket
+pub trait Listener: Send {
+    /// Blocks until the next connection arrives. Errors are logged and
+    /// don't stop the server.
+    fn accept(&self) -> io::Result<Box<dyn Connection>>;
+    /// Returns a function that makes a blocked [`Listener::accept`]
+    /// return, so that a shutdown is noticed without waiting for the next
+    /// client. The default does nothing.
+    fn waker(&self) -> io::Result<Waker> {
+        Ok(Box::new(|| {}))
+    }
+    /// The socket address, `None` for listeners that don't have one.
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+impl Connection for TcpStream {
+    fn split(self: Box<Self>) -> io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)> {
+        Ok((Box::new(self.try_clone()?), self))
+    }
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+    fn closer(&self) -> io::Result<Closer> {
+        let stream = self.try_clone()?;
+        Ok(Box::new(move |how| {
+            let _ = stream.shutdown(how);
+        }))
+    }
+}
+
+impl Listener for TcpListener {
+    fn accept(&self) -> io::Result<Box<dyn Connection>> {
+        let (stream, _) = TcpListener::accept(self)?;
+        Ok(Box::new(stream))
+    }
+    fn waker(&self) -> io::Result<Waker> {
+        let addr = wake_addr(TcpListener::local_addr(self)?);
+        // `accept` only returns on a new connection, make one.
+        Ok(Box::new(move || {
+            let _ = TcpStream::connect_timeout(&addr, Duration::from_secs(1));
+        }))
+    }
+    #[inline]
+    fn local_addr(&self) -> Option<SocketAddr> {
+        TcpListener::local_addr(self).ok()
+    }
+}
+
+fn wake_addr(mut addr: SocketAddr) -> SocketAddr {
+    if addr.ip().is_unspecified() {
+        match addr {
+            SocketAddr::V4(_) => addr.set_ip([127, 0, 0, 1].into()),
+            SocketAddr::V6(_) => addr.set_ip(std::net::Ipv6Addr::LOCALHOST.into()),
+        }
+    }
+    addr
+}
+
+/// A connection made of two independent halves.
+/// Ex: Duplex::new(pipe_reader, pipe_writer)
+pub struct Duplex<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R, W> Duplex<R, W>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    #[inline]
+    pub fn new(reader: R, writer: W) -> Self {
+        Self { reader, writer }
+    }
+}
+
+impl<R, W> Connection for Duplex<R, W>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    fn split(self: Box<Self>) -> io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)> {
+        Ok((Box::new(self.reader), Box::new(self.writer)))
+    }
+}
@@ -0,0 +1,144 @@
+use super::conn::{Closer, Waker};
+use std::{
+    collections::HashMap,
+    io,
+    net::{Shutdown, SocketAddr},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+struct Tracked {
+    closer: Closer,
+    idle: bool,
+}
+
+// Connections currently owned by a worker, so that shutdown can wake the
+// idle ones and cut the rest once the drain deadline has passed.
+pub(crate) struct ServerState {
+    stopping: AtomicBool,
+    next_id: AtomicU64,
+    connections: Mutex<HashMap<u64, Tracked>>,
+    waker: Waker,
+}
+
+impl ServerState {
+    pub(crate) fn new(waker: Waker) -> Self {
+        Self {
+            stopping: AtomicBool::new(false),
+            next_id: AtomicU64::new(0),
+            connections: Mutex::new(HashMap::new()),
+            waker,
+        }
+    }
+    #[inline]
+    pub(crate) fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+    pub(crate) fn register(&self, closer: Closer) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(id, Tracked { closer, idle: true });
+        id
+    }
+    pub(crate) fn unregister(&self, id: u64) {
+        self.connections.lock().unwrap().remove(&id);
+    }
+    // Returns `false` when the connection should be closed instead of
+    // waiting for another request.
+    pub(crate) fn set_idle(&self, id: u64, idle: bool) -> bool {
+        if let Some(conn) = self.connections.lock().unwrap().get_mut(&id) {
+            conn.idle = idle;
+        }
+        !(idle && self.is_stopping())
+    }
+    pub(crate) fn close_all(&self) {
+        for conn in self.connections.lock().unwrap().values() {
+            (conn.closer)(Shutdown::Both);
+        }
+    }
+    fn shutdown(&self) {
+        if self.stopping.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        for conn in self.connections.lock().unwrap().values() {
+            if conn.idle {
+                (conn.closer)(Shutdown::Read);
+            }
+        }
+        (self.waker)();
+    }
+}
+
+pub struct ServerHandle {
+    pub(crate) addr: Option<SocketAddr>,
+    pub(crate) state: Arc<ServerState>,
+    pub(crate) thread: JoinHandle<()>,
+}
+
+impl ServerHandle {
+    /// The address the server is bound to.
+    ///
+    /// Panics when the listener has no socket address, see
+    /// [`ServerHandle::try_local_addr`].
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+            .expect("listener is not bound to a socket address")
+    }
+    /// `None` for listeners that are not bound to a socket address.
+    /// Ex: a Unix socket, a custom [`Listener`](super::Listener)
+    #[inline]
+    pub fn try_local_addr(&self) -> Option<SocketAddr> {
+        self.addr
+    }
+    /// Stops accepting connections and lets in-flight requests finish,
+    /// connections still queued are answered with `503 Service Unavailable`.
+    /// Connections still open after the shutdown timeout are closed.
+    /// Returns immediately, use [`ServerHandle::join`] to wait.
+    pub fn shutdown(&self) {
+        self.state.shutdown();
+    }
+    #[inline]
+    pub fn is_shutting_down(&self) -> bool {
+        self.state.is_stopping()
+    }
+    /// Triggers [`ServerHandle::shutdown`] on `SIGINT` or `SIGTERM`. Once
+    /// the shutdown has begun, a second signal kills the process as usual.
+    #[cfg(unix)]
+    pub fn shutdown_on_signal(&self) {
+        let seen = super::signal::install();
+        let state = self.state.clone();
+        thread::spawn(move || {
+            while !state.is_stopping() {
+                if super::signal::received_since(seen) {
+                    state.shutdown();
+                    break;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            super::signal::uninstall();
+        });
+    }
+    /// Blocks until the server has stopped and every connection is closed.
+    /// Handlers still running past the shutdown timeout are left behind.
+    pub fn join(self) -> thread::Result<()> {
+        self.thread.join()
+    }
+    // For the `listen` functions, a panic of the server thread becomes
+    // their error.
+    pub(crate) fn wait(self) -> io::Result<()> {
+        self.join().map_err(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or(panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            io::Error::other(format!("server thread panicked: {}", message))
+        })
+    }
+}
@@ -1,49 +1,318 @@
 use std::{
-    collections::HashMap,
-    io::{self, BufRead, BufReader, BufWriter, Read},
-    net::{TcpListener, TcpStream},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    net::Shutdown,
+    panic::{self, AssertUnwindSafe},
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, Mutex,
+        atomic::Ordering,
+        mpsc::{self, RecvTimeoutError},
+    },
     thread,
+    time::{Duration, Instant},
 };
 
-use crate::{App, error::HttpError, method::Method, request::HttpRequest, response::HttpResponse};
+use crate::{
+    App,
+    app::Route,
+    body::{self, BodyReader, DEFAULT_BODY_LIMIT, Limits},
+    error::HttpError,
+    header::{self, HeaderMap},
+    method::Method,
+    mime::MimeType,
+    request::HttpRequest,
+    response::{HttpResponse, REQUEST_HEADERS, ResponseState},
+    status::Status,
+    url,
+};
+
+mod conn;
+mod handle;
+mod pool;
+#[cfg(unix)]
+mod signal;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(unix)]
+mod unix;
+
+pub use conn::{Closer, Connection, Duplex, Listener, Waker};
+pub use handle::ServerHandle;
+pub(crate) use handle::ServerState;
+use pool::ThreadPool;
+pub use pool::{QueuePolicy, ServerMetrics};
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+#[cfg(feature = "tls")]
+pub(crate) use tls::TlsListener;
+#[cfg(unix)]
+pub(crate) use unix::UnixSocket;
+
+// The buffered halves of a connection, whatever the transport.
+pub type Reader = BufReader<Box<dyn Read + Send>>;
+pub type Writer = BufWriter<Box<dyn Write + Send>>;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ServerConfig {
+    pub(crate) body_limit: usize,
+    pub(crate) limits: Limits,
+    pub(crate) etag: bool,
+    pub(crate) keep_alive_timeout: Duration,
+    pub(crate) max_requests: usize,
+    pub(crate) workers: usize,
+    pub(crate) queue_size: usize,
+    pub(crate) queue_policy: QueuePolicy,
+    pub(crate) shutdown_timeout: Duration,
+    // Permission bits of the socket file, the umask decides when `None`.
+    #[cfg(unix)]
+    pub(crate) socket_mode: Option<u32>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            body_limit: DEFAULT_BODY_LIMIT,
+            limits: Limits::default(),
+            etag: true,
+            keep_alive_timeout: Duration::from_secs(5),
+            max_requests: 100,
+            workers: thread::available_parallelism().map_or(4, |n| n.get() * 4),
+            queue_size: 128,
+            queue_policy: QueuePolicy::Block,
+            shutdown_timeout: Duration::from_secs(30),
+            #[cfg(unix)]
+            socket_mode: None,
+        }
+    }
+}
 
 pub(crate) struct HttpServer<R, W>
 where
     R: io::Read,
     W: io::Write,
 {
-    pub(crate) listener: TcpListener,
+    pub(crate) listener: Box<dyn Listener>,
     pub(crate) handler: Arc<App<R, W>>,
+    pub(crate) state: Arc<ServerState>,
 }
 
-impl HttpServer<BufReader<TcpStream>, BufWriter<TcpStream>> {
+impl HttpServer<Reader, Writer> {
     pub(crate) fn run(self) {
         let handler = self.handler;
-        for stream in self.listener.incoming() {
+        let state = self.state;
+        let config = handler.config.read().unwrap().clone();
+        let pool = {
+            let handler = handler.clone();
+            let state = state.clone();
+            ThreadPool::new(
+                config.workers,
+                config.queue_size,
+                handler.metrics.clone(),
+                move |stream| {
+                    let _ = handle_stream(handler.clone(), &state, stream);
+                },
+            )
+        };
+        // Answering `503` takes a moment, never on the accepting thread.
+        // Past a few waiting, rejected connections are just closed.
+        let rejecter = ThreadPool::new(1, REJECT_QUEUE, ServerMetrics::default(), |stream| {
+            let _ = reject(stream);
+        });
+        loop {
+            let stream = self.listener.accept();
+            if state.is_stopping() {
+                break;
+            }
             match stream {
-                Ok(stream) => {
-                    let handler = handler.clone();
-                    thread::spawn(move || {
-                        let handler = handler;
-                        let _ = handle_stream(handler, stream);
-                    });
-                }
+                Ok(stream) => match config.queue_policy {
+                    QueuePolicy::Block => pool.execute(stream),
+                    QueuePolicy::Reject => {
+                        if let Err(stream) = pool.try_execute(stream) {
+                            handler.metrics.inc_rejected();
+                            let _ = rejecter.try_execute(stream);
+                        }
+                    }
+                },
                 Err(err) => eprintln!("{}\n", err),
             }
         }
+        drop(self.listener);
+        // Let the workers drain in-flight requests until the deadline.
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            drop(pool);
+            let _ = tx.send(());
+        });
+        if rx.recv_timeout(config.shutdown_timeout).is_err() {
+            state.close_all();
+        }
     }
 }
 
+const REJECT_QUEUE: usize = 8;
+const REJECT_LINGER: Duration = Duration::from_millis(250);
+const REJECT_DEADLINE: Duration = Duration::from_secs(1);
+
+// Rejections are answered one at a time, a client that stalls (Ex: in the
+// middle of a TLS handshake) is cut off at the deadline.
+fn reject(conn: Box<dyn Connection>) -> io::Result<()> {
+    let closer = conn.closer()?;
+    let (done, wait) = mpsc::channel::<()>();
+    thread::scope(|scope| {
+        let closer = &closer;
+        scope.spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = wait.recv_timeout(REJECT_DEADLINE) {
+                closer(Shutdown::Both);
+            }
+        });
+        let result = answer_503(conn, closer);
+        drop(done);
+        result
+    })
+}
+
+// The request is read for a moment after answering, closing with unread
+// data makes the kernel reset the connection, which can destroy the `503`
+// before the client sees it.
+fn answer_503(conn: Box<dyn Connection>, closer: &Closer) -> io::Result<()> {
+    conn.set_read_timeout(Some(REJECT_LINGER))?;
+    let (mut reader, writer) = conn.split()?;
+    let writer = Arc::new(Mutex::new(BufWriter::new(writer)));
+    let res = HttpResponse::new(writer.clone());
+    res.status(Status::ServiceUnavailable);
+    res.send("")?;
+    writer.lock().unwrap().flush()?;
+    closer(Shutdown::Write);
+    let deadline = Instant::now() + REJECT_LINGER;
+    let mut buf = [0; 4096];
+    while Instant::now() < deadline && matches!(reader.read(&mut buf), Ok(n) if n > 0) {}
+    closer(Shutdown::Both);
+    Ok(())
+}
+
 fn handle_stream(
-    handler: Arc<App<BufReader<TcpStream>, BufWriter<TcpStream>>>,
-    stream: TcpStream,
+    handler: Arc<App<Reader, Writer>>,
+    state: &ServerState,
+    conn: Box<dyn Connection>,
+) -> io::Result<()> {
+    // Still queued when the shutdown began, the client may retry elsewhere.
+    if state.is_stopping() {
+        return reject(conn);
+    }
+    let id = state.register(conn.closer()?);
+    // Unregistering drops the closer's handle on the socket, without it the
+    // connection would stay open even after a panic.
+    let _registered = Registered { state, id };
+    serve_connection(handler, state, id, conn)
+}
+
+struct Registered<'a> {
+    state: &'a ServerState,
+    id: u64,
+}
+
+impl Drop for Registered<'_> {
+    fn drop(&mut self) {
+        self.state.unregister(self.id);
+    }
+}
+
+pub(crate) fn serve_connection(
+    handler: Arc<App<Reader, Writer>>,
+    state: &ServerState,
+    id: u64,
+    conn: Box<dyn Connection>,
+) -> io::Result<()> {
+    let config = handler.config.read().unwrap().clone();
+    conn.set_read_timeout(Some(config.keep_alive_timeout))?;
+    let (reader, writer) = conn.split()?;
+    let reader = Arc::new(Mutex::new(BufReader::new(reader)));
+    let writer = Arc::new(Mutex::new(BufWriter::new(writer)));
+    let mut served = 0;
+    loop {
+        if !state.set_idle(id, true) {
+            return Ok(());
+        }
+        let mut req = match get_req(reader.clone()) {
+            Ok(Some(req)) => req,
+            Ok(None) => return Ok(()),
+            Err(HttpError::StdError(err)) if !is_timeout(&err) => return Err(err),
+            // The connection can't be trusted anymore, answer and close it.
+            Err(err) => return send_error(&handler, &writer, err),
+        };
+        state.set_idle(id, false);
+        served += 1;
+        req.set_body_limit(config.body_limit);
+        req.limits = config.limits.clone();
+        let keep_alive =
+            served < config.max_requests && !state.is_stopping() && wants_keep_alive(&req);
+        let explicit = req.version == "HTTP/1.0";
+        let body = req.body.clone();
+        let mut res = HttpResponse::new(writer.clone());
+        res.connection = Arc::new(ResponseState::new(keep_alive, explicit));
+        res.head = req.method == Method::Head;
+        res.method = req.method.clone();
+        res.etag = config.etag;
+        for name in REQUEST_HEADERS {
+            if let Some(value) = req.header.get(name) {
+                res.request_header.append(name, value);
+            }
+        }
+        let connection = res.connection.clone();
+        // A panicking handler fails its request like an error would, the
+        // client gets a `500` if nothing was sent yet and the connection is
+        // closed either way.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| dispatch(&handler, req, res)))
+            .unwrap_or_else(|_| {
+                writer.clear_poison();
+                Err(io::Error::other("handler panicked"))
+            });
+        if let Err(err) = result {
+            if connection.sent.load(Ordering::Relaxed) {
+                return Err(err);
+            }
+            return send_error(&handler, &writer, err.into());
+        }
+        writer.lock().unwrap().flush()?;
+        if !connection.sent.load(Ordering::Relaxed)
+            || !connection.keep_alive.load(Ordering::Relaxed)
+        {
+            return Ok(());
+        }
+        // Whatever the handler left of the body must go before the next
+        // request can be parsed, give up on the connection if it can't.
+        let mut rest = BodyReader {
+            reader: &reader,
+            state: &body,
+        };
+        if io::copy(&mut rest, &mut io::sink()).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+fn wants_keep_alive<R>(req: &HttpRequest<R>) -> bool {
+    let connection = req
+        .header
+        .get(header::CONNECTION)
+        .map(str::to_ascii_lowercase);
+    let has = |token: &str| {
+        connection
+            .as_deref()
+            .is_some_and(|value| value.split(',').any(|v| v.trim() == token))
+    };
+    match req.version.as_str() {
+        "HTTP/1.1" => !has("close"),
+        "HTTP/1.0" => has("keep-alive"),
+        _ => false,
+    }
+}
+
+fn dispatch(
+    handler: &App<Reader, Writer>,
+    mut req: HttpRequest<Reader>,
+    mut res: HttpResponse<Writer>,
 ) -> io::Result<()> {
-    let req_stream = stream.try_clone().unwrap();
-    let res_strean = Arc::new(Mutex::new(BufWriter::new(stream)));
-    let mut req = get_req(Arc::new(Mutex::new(BufReader::new(req_stream)))).unwrap();
-    let mut res = HttpResponse::new(res_strean.clone());
     for f in handler.middleware.read().unwrap().iter() {
         match f(req, res) {
             Ok(Some((r, s))) => {
@@ -58,97 +327,163 @@ fn handle_stream(
             }
         }
     }
-    match &req.method {
-        Method::Connect => {
-            if let Some(f) = handler.connect.read().unwrap().get(req.path.as_str()) {
-                f(req, res)?;
-            } else if let Some(not_found) = &handler.unknown.read().unwrap().as_ref() {
-                not_found(req, res)?;
-            }
-        }
-        Method::Get => {
-            if let Some(f) = handler.get.read().unwrap().get(req.path.as_str()) {
-                f(req, res)?;
-            } else if let Some(not_found) = &handler.unknown.read().unwrap().as_ref() {
-                not_found(req, res)?;
-            }
-        }
-        Method::Post => {
-            if let Some(f) = handler.post.read().unwrap().get(req.path.as_str()) {
-                f(req, res)?;
-            } else if let Some(not_found) = &handler.unknown.read().unwrap().as_ref() {
-                not_found(req, res)?;
-            }
-        }
-        Method::Delete => {
-            if let Some(f) = handler.delete.read().unwrap().get(req.path.as_str()) {
-                f(req, res)?;
-            } else if let Some(not_found) = &handler.unknown.read().unwrap().as_ref() {
-                not_found(req, res)?;
-            }
-        }
-        Method::Head => {
-            if let Some(f) = handler.head.read().unwrap().get(req.path.as_str()) {
-                f(req, res)?;
-            } else if let Some(not_found) = &handler.unknown.read().unwrap().as_ref() {
-                not_found(req, res)?;
-            }
-        }
-        Method::Put => {
-            if let Some(f) = handler.put.read().unwrap().get(req.path.as_str()) {
-                f(req, res)?;
-            } else if let Some(not_found) = &handler.unknown.read().unwrap().as_ref() {
-                not_found(req, res)?;
-            }
-        }
-        Method::Patch => {
-            if let Some(f) = handler.patch.read().unwrap().get(req.path.as_str()) {
-                f(req, res)?;
-            } else if let Some(not_found) = &handler.unknown.read().unwrap().as_ref() {
-                not_found(req, res)?;
-            }
+    let router = handler.router.read().unwrap();
+    match router.find(&req.method, &req.path) {
+        Route::Found(f, params) => {
+            req.params = params;
+            f(req, res)?;
         }
-        Method::Trace => {
-            if let Some(f) = handler.trace.read().unwrap().get(req.path.as_str()) {
-                f(req, res)?;
-            } else if let Some(not_found) = &handler.unknown.read().unwrap().as_ref() {
-                not_found(req, res)?;
-            }
+        Route::MethodNotAllowed(allow) => {
+            let allow: Vec<_> = allow.iter().map(Method::to_string).collect();
+            res.insert_header(header::ALLOW, allow.join(", "))?;
+            error_page(handler, res, &HttpError::MethodNotAllowed)?;
         }
-        Method::Options => {
-            if let Some(f) = handler.options.read().unwrap().get(req.path.as_str()) {
-                f(req, res)?;
-            } else if let Some(not_found) = &handler.unknown.read().unwrap().as_ref() {
-                not_found(req, res)?;
+        Route::NotFound => match handler.unknown.read().unwrap().as_ref() {
+            Some(fallback) => fallback(req, res)?,
+            None => {
+                res.status(Status::NotFound);
+                res.content_type(MimeType::TextPlain);
+                res.send(Status::NotFound)?;
             }
-        }
+        },
     }
     Ok(())
 }
+
+const MAX_REQUEST_LINE: u64 = 8 * 1024;
+const MAX_HEADER_SIZE: u64 = 64 * 1024;
+
 fn get_req<W: io::Read>(
     r: Arc<Mutex<BufReader<W>>>,
-) -> Result<HttpRequest<BufReader<W>>, HttpError> {
+) -> Result<Option<HttpRequest<BufReader<W>>>, HttpError> {
     let cloned_r = r.clone();
     let mut reader = cloned_r.lock().unwrap();
-    let first_line = match reader.by_ref().lines().next() {
-        Some(Ok(line)) => line,
-        Some(Err(err)) => return Err(err.into()),
-        None => return Err(HttpError::BadRequest),
+    // The peer closed the connection or stayed idle between two requests.
+    match reader.fill_buf() {
+        Ok([]) => return Ok(None),
+        Ok(_) => {}
+        Err(err) if is_timeout(&err) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    // Ex: GET /index.html HTTP/1.1
+    let first_line = read_line(&mut *reader, MAX_REQUEST_LINE).map_err(|err| match err {
+        HttpError::HeaderTooLarge => HttpError::UriTooLong,
+        err => err,
+    })?;
+    let mut iter = first_line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (iter.next(), iter.next(), iter.next(), iter.next())
+    else {
+        return Err(HttpError::BadRequest);
     };
-    let mut iter = first_line.split_whitespace();
-    let method = iter.next().unwrap_or_default();
-    let method = Method::from_str(method)?;
-    let path = iter.next().unwrap_or_default().to_owned();
-    let version = iter.next().unwrap_or_default().to_owned();
-    let mut header = HashMap::new();
-    for line in reader.by_ref().lines() {
-        let line = line?;
-        if line.trim().is_empty() {
+    let method = Method::from_str(method).map_err(|err| {
+        // A well formed but unknown method is `501`, anything else is garbage.
+        if header::is_token(method) {
+            err
+        } else {
+            HttpError::BadRequest
+        }
+    })?;
+    let target = parse_target(target)?;
+    let version = parse_version(version)?;
+    let mut header = HeaderMap::new();
+    let mut size = first_line.len() as u64;
+    loop {
+        let line = read_line(&mut *reader, MAX_HEADER_SIZE.saturating_sub(size))?;
+        size += line.len() as u64 + 2;
+        if line.is_empty() {
             break;
         }
-        if let Some((key, value)) = line.split_once(":") {
-            header.insert(key.trim().to_owned(), value.trim().to_owned());
+        let Some((key, value)) = line.split_once(':') else {
+            return Err(HttpError::BadRequest);
+        };
+        if key.is_empty() || key.ends_with([' ', '\t']) {
+            return Err(HttpError::BadRequest);
+        }
+        header.append(key, value.trim());
+    }
+    body::check_framing(&header)?;
+    Ok(Some(HttpRequest::new(method, target, version, header, r)))
+}
+
+fn read_line<R: BufRead>(reader: &mut R, limit: u64) -> Result<String, HttpError> {
+    let mut line = Vec::new();
+    let n = io::Read::take(&mut *reader, limit + 2).read_until(b'\n', &mut line)?;
+    if n == 0 {
+        return Err(HttpError::BadRequest);
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(if n as u64 > limit {
+            HttpError::HeaderTooLarge
+        } else {
+            HttpError::BadRequest
+        });
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| HttpError::BadRequest)
+}
+
+// Ex: /path?query, * or http://host/path?query
+fn parse_target(target: &str) -> Result<String, HttpError> {
+    let target = if target.starts_with('/') || target == "*" {
+        target.to_owned()
+    } else {
+        let rest = target
+            .strip_prefix("http://")
+            .or_else(|| target.strip_prefix("https://"))
+            .ok_or(HttpError::BadRequest)?;
+        match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('/') => rest[i..].to_owned(),
+            Some(i) => format!("/{}", &rest[i..]),
+            None => "/".to_owned(),
+        }
+    };
+    // The request decodes it again, refuse what it could only guess at.
+    url::decode_path(target.split('?').next().unwrap())?;
+    Ok(target)
+}
+
+fn parse_version(version: &str) -> Result<String, HttpError> {
+    match version.strip_prefix("HTTP/").map(str::as_bytes) {
+        Some(b"1.0" | b"1.1") => Ok(version.to_owned()),
+        Some([major, b'.', minor]) if major.is_ascii_digit() && minor.is_ascii_digit() => {
+            Err(HttpError::InvalidHttpVersion)
+        }
+        _ => Err(HttpError::BadRequest),
+    }
+}
+
+#[inline]
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+fn send_error(
+    handler: &App<Reader, Writer>,
+    writer: &Arc<Mutex<Writer>>,
+    err: HttpError,
+) -> io::Result<()> {
+    error_page(handler, HttpResponse::new(writer.clone()), &err)?;
+    writer.lock().unwrap().flush()
+}
+
+fn error_page(
+    handler: &App<Reader, Writer>,
+    res: HttpResponse<Writer>,
+    err: &HttpError,
+) -> io::Result<()> {
+    res.status(err.status());
+    match handler.error_handler.read().unwrap().as_ref() {
+        Some(f) => f(err, res),
+        None => {
+            res.content_type(MimeType::TextPlain);
+            res.send(err.status())
         }
     }
-    Ok(HttpRequest::new(method, path, version, header, r))
 }
@@ -0,0 +1,126 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+    },
+    thread::{self, JoinHandle},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueuePolicy {
+    /// Stop accepting until a worker frees a slot.
+    #[default]
+    Block,
+    /// Answer with `503 Service Unavailable` and close the connection.
+    Reject,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    workers: AtomicUsize,
+    busy: AtomicUsize,
+    queued: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ServerMetrics(Arc<Counters>);
+
+impl ServerMetrics {
+    #[inline]
+    pub fn workers(&self) -> usize {
+        self.0.workers.load(Ordering::Relaxed)
+    }
+    #[inline]
+    pub fn busy_workers(&self) -> usize {
+        self.0.busy.load(Ordering::Relaxed)
+    }
+    #[inline]
+    pub fn queue_depth(&self) -> usize {
+        self.0.queued.load(Ordering::Relaxed)
+    }
+    #[inline]
+    pub fn rejected(&self) -> usize {
+        self.0.rejected.load(Ordering::Relaxed)
+    }
+    #[inline]
+    pub(crate) fn inc_rejected(&self) {
+        self.0.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) struct ThreadPool<T> {
+    sender: Option<SyncSender<T>>,
+    workers: Vec<JoinHandle<()>>,
+    metrics: ServerMetrics,
+}
+
+impl<T: Send + 'static> ThreadPool<T> {
+    pub(crate) fn new<F>(size: usize, queue_size: usize, metrics: ServerMetrics, f: F) -> Self
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let size = size.max(1);
+        let (sender, receiver) = mpsc::sync_channel(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let f = Arc::new(f);
+        let workers = (0..size)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let metrics = metrics.clone();
+                let f = f.clone();
+                thread::spawn(move || worker(receiver, metrics, f))
+            })
+            .collect();
+        metrics.0.workers.store(size, Ordering::Relaxed);
+        Self {
+            sender: Some(sender),
+            workers,
+            metrics,
+        }
+    }
+    pub(crate) fn execute(&self, job: T) {
+        self.metrics.0.queued.fetch_add(1, Ordering::Relaxed);
+        if self.sender.as_ref().unwrap().send(job).is_err() {
+            self.metrics.0.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+    // Gives the job back when the queue is full.
+    pub(crate) fn try_execute(&self, job: T) -> Result<(), T> {
+        self.metrics.0.queued.fetch_add(1, Ordering::Relaxed);
+        match self.sender.as_ref().unwrap().try_send(job) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(job) | TrySendError::Disconnected(job)) => {
+                self.metrics.0.queued.fetch_sub(1, Ordering::Relaxed);
+                Err(job)
+            }
+        }
+    }
+}
+
+fn worker<T, F: Fn(T)>(receiver: Arc<Mutex<Receiver<T>>>, metrics: ServerMetrics, f: Arc<F>) {
+    loop {
+        let job = receiver.lock().unwrap().recv();
+        let Ok(job) = job else {
+            break;
+        };
+        metrics.0.queued.fetch_sub(1, Ordering::Relaxed);
+        metrics.0.busy.fetch_add(1, Ordering::Relaxed);
+        // A panicking handler must not take the worker down with it.
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| f(job)));
+        metrics.0.busy.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<T> Drop for ThreadPool<T> {
+    // Lets the workers finish what is queued, then waits for them.
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        self.metrics.0.workers.store(0, Ordering::Relaxed);
+    }
+}
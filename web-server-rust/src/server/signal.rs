@@ -0,0 +1,100 @@
+use std::{
+    ffi::c_int,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+// From each platform's <signal.h>, as the `libc` crate has them.
+#[allow(non_camel_case_types)]
+type sighandler_t = usize;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod consts {
+    use super::{c_int, sighandler_t};
+    pub(super) const SIGINT: c_int = 2;
+    pub(super) const SIGTERM: c_int = 15;
+    pub(super) const SIG_DFL: sighandler_t = 0;
+}
+
+// Apple, the BSDs and Solaris keep the historical numbering.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod consts {
+    use super::{c_int, sighandler_t};
+    pub(super) const SIGINT: c_int = 2;
+    pub(super) const SIGTERM: c_int = 15;
+    pub(super) const SIG_DFL: sighandler_t = 0;
+}
+
+use consts::{SIG_DFL, SIGINT, SIGTERM};
+
+// Signals caught so far. Each watcher compares against the count it saw
+// when installed, so an old signal never stops a later server.
+static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+// Servers waiting for a signal, the default action is back once none are.
+static WATCHERS: Mutex<usize> = Mutex::new(0);
+
+unsafe extern "C" {
+    fn signal(signum: c_int, handler: sighandler_t) -> sighandler_t;
+}
+
+// Only async-signal-safe work here, the watcher thread does the rest. The
+// default action is restored right away, so that a second signal kills a
+// process whose shutdown hangs.
+extern "C" fn on_signal(signum: c_int) {
+    RECEIVED.fetch_add(1, Ordering::SeqCst);
+    unsafe {
+        signal(signum, SIG_DFL);
+    }
+}
+
+// Returns the count to pass to `received_since`.
+pub(crate) fn install() -> usize {
+    let mut watchers = WATCHERS.lock().unwrap();
+    *watchers += 1;
+    let handler = on_signal as extern "C" fn(c_int) as sighandler_t;
+    unsafe {
+        signal(SIGINT, handler);
+        signal(SIGTERM, handler);
+    }
+    RECEIVED.load(Ordering::SeqCst)
+}
+
+pub(crate) fn uninstall() {
+    let mut watchers = WATCHERS.lock().unwrap();
+    *watchers -= 1;
+    if *watchers == 0 {
+        unsafe {
+            signal(SIGINT, SIG_DFL);
+            signal(SIGTERM, SIG_DFL);
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn received_since(count: usize) -> bool {
+    RECEIVED.load(Ordering::SeqCst) != count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" {
+        fn raise(signum: c_int) -> c_int;
+    }
+
+    #[test]
+    fn signals_are_scoped_to_their_watcher() {
+        let seen = install();
+        assert!(!received_since(seen));
+        assert_eq!(unsafe { raise(SIGTERM) }, 0);
+        assert!(received_since(seen));
+        uninstall();
+        // A later watcher doesn't see the signal from before.
+        let seen = install();
+        assert!(!received_since(seen));
+        uninstall();
+    }
+}
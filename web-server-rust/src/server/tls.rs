@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use rustls::{
+    ServerConfig, ServerConnection, StreamOwned,
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+
+use super::conn::{Closer, Connection, Listener, Waker};
+
+// Certificates served over TLS, picked by the SNI name the client sends
+// and falling back to the default one.
+#[derive(Clone)]
+pub struct TlsConfig {
+    default: Option<Arc<CertifiedKey>>,
+    hosts: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl TlsConfig {
+    pub fn new<P: AsRef<Path>, K: AsRef<Path>>(cert: P, key: K) -> io::Result<Self> {
+        Ok(Self {
+            default: Some(load(cert.as_ref(), key.as_ref())?),
+            hosts: HashMap::new(),
+        })
+    }
+    // Without a default certificate, clients that send no or an unknown
+    // server name fail the handshake.
+    pub fn sni_only() -> Self {
+        Self {
+            default: None,
+            hosts: HashMap::new(),
+        }
+    }
+    pub fn add_host<P: AsRef<Path>, K: AsRef<Path>>(
+        mut self,
+        name: &str,
+        cert: P,
+        key: K,
+    ) -> io::Result<Self> {
+        let key = load(cert.as_ref(), key.as_ref())?;
+        self.hosts.insert(name.to_ascii_lowercase(), key);
+        Ok(self)
+    }
+    pub(crate) fn build(self) -> Arc<ServerConfig> {
+        let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(self));
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Arc::new(config)
+    }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("default", &self.default.is_some())
+            .field("hosts", &self.hosts.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ResolvesServerCert for TlsConfig {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        client_hello
+            .server_name()
+            .and_then(|name| self.hosts.get(&name.to_ascii_lowercase()))
+            .or(self.default.as_ref())
+            .cloned()
+    }
+}
+
+fn load(cert: &Path, key: &Path) -> io::Result<Arc<CertifiedKey>> {
+    let invalid = |err: rustls::pki_types::pem::Error| {
+        io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+    };
+    let certs = CertificateDer::pem_file_iter(cert)
+        .map_err(invalid)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(invalid)?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no certificate found in {}", cert.display()),
+        ));
+    }
+    let key = PrivateKeyDer::from_pem_file(key).map_err(invalid)?;
+    let key = ring::sign::any_supported_type(&key)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(Arc::new(CertifiedKey::new(certs, key)))
+}
+
+pub(crate) struct TlsListener {
+    pub(crate) inner: TcpListener,
+    pub(crate) config: Arc<ServerConfig>,
+}
+
+impl Listener for TlsListener {
+    fn accept(&self) -> io::Result<Box<dyn Connection>> {
+        let (stream, _) = self.inner.accept()?;
+        let conn = ServerConnection::new(self.config.clone())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        // The handshake happens on the first read or write, in the worker.
+        Ok(Box::new(TlsStream {
+            tcp: stream.try_clone()?,
+            inner: Arc::new(Mutex::new(StreamOwned::new(conn, stream))),
+        }))
+    }
+    #[inline]
+    fn waker(&self) -> io::Result<Waker> {
+        self.inner.waker()
+    }
+    #[inline]
+    fn local_addr(&self) -> Option<SocketAddr> {
+        Listener::local_addr(&self.inner)
+    }
+}
+
+struct TlsStream {
+    tcp: TcpStream,
+    inner: Arc<Mutex<StreamOwned<ServerConnection, TcpStream>>>,
+}
+
+impl Connection for TlsStream {
+    fn split(self: Box<Self>) -> io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)> {
+        Ok((
+            Box::new(TlsHalf {
+                stream: self.inner.clone(),
+                writer: false,
+            }),
+            Box::new(TlsHalf {
+                stream: self.inner,
+                writer: true,
+            }),
+        ))
+    }
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.tcp.set_read_timeout(timeout)
+    }
+    fn closer(&self) -> io::Result<Closer> {
+        let stream = self.tcp.try_clone()?;
+        Ok(Box::new(move |how: Shutdown| {
+            let _ = stream.shutdown(how);
+        }))
+    }
+}
+
+// Both halves share the session, a connection is only ever read from or
+// written to by its worker so the lock is never contended.
+struct TlsHalf {
+    stream: Arc<Mutex<StreamOwned<ServerConnection, TcpStream>>>,
+    writer: bool,
+}
+
+impl Read for TlsHalf {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.lock().unwrap().read(buf)
+    }
+}
+
+impl Write for TlsHalf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.lock().unwrap().flush()
+    }
+}
+
+impl Drop for TlsHalf {
+    fn drop(&mut self) {
+        // The writer going away ends the connection, say so to the peer.
+        // Also while unwinding from a panic that poisoned the lock.
+        if self.writer {
+            let mut stream = self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+            stream.conn.send_close_notify();
+            let _ = stream.flush();
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Read, Write},
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use super::conn::{Closer, Connection, Listener, Waker};
+
+// Owns the socket file, which is removed once the server stops.
+pub(crate) struct UnixSocket {
+    inner: UnixListener,
+    path: PathBuf,
+}
+
+impl UnixSocket {
+    pub(crate) fn bind(path: &Path, mode: Option<u32>) -> io::Result<Self> {
+        remove_stale(path)?;
+        let inner = match mode {
+            Some(mode) => bind_private(path, mode)?,
+            None => UnixListener::bind(path)?,
+        };
+        Ok(Self {
+            inner,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+// A socket is created with the permissions the umask allows, and would be
+// reachable with those until `chmod`. Bind it in a directory only the owner
+// can enter instead, and link it into place once it has its final mode.
+fn bind_private(path: &Path, mode: u32) -> io::Result<UnixListener> {
+    let Some(name) = path.file_name() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        ));
+    };
+    let mut dir = OsString::from(".");
+    dir.push(name);
+    dir.push(format!(".{}", process::id()));
+    let dir = path.with_file_name(dir);
+    fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let private = dir.join("s");
+    let listener = UnixListener::bind(&private).and_then(|listener| {
+        fs::set_permissions(&private, fs::Permissions::from_mode(mode))?;
+        // Unlike `rename`, fails instead of replacing a file created since
+        // `remove_stale`.
+        fs::hard_link(&private, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&private);
+    let _ = fs::remove_dir(&dir);
+    listener
+}
+
+// A socket file left behind by a process that did not exit cleanly makes
+// `bind` fail. Remove it, unless something is still listening on it.
+fn remove_stale(path: &Path) -> io::Result<()> {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if !meta.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    // Only a refused connection means nobody is listening, any other error
+    // (Ex: no permission to connect) says nothing about it.
+    match UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use", path.display()),
+        )),
+        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path),
+        Err(err) => Err(err),
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Listener for UnixSocket {
+    fn accept(&self) -> io::Result<Box<dyn Connection>> {
+        let (stream, _) = self.inner.accept()?;
+        Ok(Box::new(stream))
+    }
+    fn waker(&self) -> io::Result<Waker> {
+        let path = self.path.clone();
+        Ok(Box::new(move || {
+            let _ = UnixStream::connect(&path);
+        }))
+    }
+}
+
+impl Connection for UnixStream {
+    fn split(self: Box<Self>) -> io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)> {
+        Ok((Box::new(self.try_clone()?), self))
+    }
+    #[inline]
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+    fn closer(&self) -> io::Result<Closer> {
+        let stream = self.try_clone()?;
+        Ok(Box::new(move |how| {
+            let _ = stream.shutdown(how);
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socket_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("web-server-unix-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn stale_socket_is_replaced() {
+        let path = socket_path("stale.sock");
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let socket = UnixSocket::bind(&path, None).unwrap();
+        UnixStream::connect(&path).unwrap();
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn socket_in_use_is_kept() {
+        let path = socket_path("busy.sock");
+        let _ = fs::remove_file(&path);
+        let _busy = UnixListener::bind(&path).unwrap();
+        let err = UnixSocket::bind(&path, None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(path.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn other_files_are_kept() {
+        let path = socket_path("file.sock");
+        fs::write(&path, "data").unwrap();
+        let err = UnixSocket::bind(&path, None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "data");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn mode_is_set_before_the_socket_is_reachable() {
+        let path = socket_path("mode.sock");
+        let socket = UnixSocket::bind(&path, Some(0o600)).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        UnixStream::connect(&path).unwrap();
+        // The private directory is gone.
+        let entries = fs::read_dir(path.parent().unwrap()).unwrap();
+        assert!(entries.flatten().all(|entry| !entry.path().is_dir()));
+        drop(socket);
+    }
+}
@@ -0,0 +1,234 @@
+#![allow(clippy::type_complexity)]
+
+mod store;
+
+use std::{
+    collections::HashMap,
+    fmt, io,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    cookie::{Cookie, SameSite},
+    crypto,
+    header::{self, HeaderMap},
+    request::HttpRequest,
+    response::HttpResponse,
+    server::{Reader, Writer},
+};
+
+pub use store::{FileStore, MemoryStore, SessionData, SessionStore};
+
+const ID_LEN: usize = 43;
+
+struct Options {
+    store: Box<dyn SessionStore>,
+    cookie_name: String,
+    ttl: Duration,
+    secure: bool,
+}
+
+// Session middleware, a `sid` cookie holds the ID of the session found in
+// the store.
+// Ex:
+//     let sessions = Sessions::new(MemoryStore::new()).ttl(Duration::from_secs(3600));
+//     app.use_middleware(sessions.middleware());
+//     app.get("/", |req, res| {
+//         let session = req.session().unwrap();
+//         let visits = session.get::<u32>("visits").unwrap_or(0) + 1;
+//         session.set("visits", visits)?;
+//         res.send(visits)
+//     });
+pub struct Sessions {
+    options: Options,
+}
+
+impl Sessions {
+    pub fn new<S: SessionStore + 'static>(store: S) -> Self {
+        Self {
+            options: Options {
+                store: Box::new(store),
+                cookie_name: "sid".to_owned(),
+                ttl: Duration::from_secs(24 * 60 * 60),
+                secure: false,
+            },
+        }
+    }
+    pub fn cookie_name<N: Into<String>>(mut self, name: N) -> Self {
+        self.options.cookie_name = name.into();
+        self
+    }
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.options.ttl = ttl;
+        self
+    }
+    // Only send the cookie over https.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.options.secure = secure;
+        self
+    }
+    pub fn middleware(
+        self,
+    ) -> impl Fn(
+        HttpRequest<Reader>,
+        HttpResponse<Writer>,
+    ) -> io::Result<Option<(HttpRequest<Reader>, HttpResponse<Writer>)>>
+    + Send
+    + Sync
+    + 'static {
+        let options = Arc::new(self.options);
+        move |mut req, res| {
+            let found = req
+                .cookie(&options.cookie_name)
+                .filter(|id| is_valid_id(id))
+                .map(|id| {
+                    let data = options.store.load(id)?;
+                    Ok::<_, io::Error>(data.map(|data| (id.to_owned(), data)))
+                })
+                .transpose()?
+                .flatten();
+            let (id, data, stored) = match found {
+                Some((id, data)) => (id, data, true),
+                None => (new_id(), HashMap::new(), false),
+            };
+            req.session = Some(Session {
+                inner: Arc::new(Inner {
+                    id: Mutex::new(id),
+                    data: Mutex::new(data),
+                    stored: Mutex::new(stored),
+                    options: options.clone(),
+                    header: res.header.clone(),
+                }),
+            });
+            Ok(Some((req, res)))
+        }
+    }
+}
+
+struct Inner {
+    id: Mutex<String>,
+    data: Mutex<SessionData>,
+    // Known to the store and the client, a new session is only saved and
+    // given a cookie once something is set.
+    stored: Mutex<bool>,
+    options: Arc<Options>,
+    header: Arc<Mutex<HeaderMap>>,
+}
+
+// The session of the current request. Changes are saved right away, the
+// cookie goes out with the response head, so set values before sending.
+#[derive(Clone)]
+pub struct Session {
+    inner: Arc<Inner>,
+}
+
+impl Session {
+    #[inline]
+    pub fn id(&self) -> String {
+        self.inner.id.lock().unwrap().clone()
+    }
+    // Ex: session.get::<u32>("visits")
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.inner.data.lock().unwrap().get(key)?.parse().ok()
+    }
+    #[inline]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.inner.data.lock().unwrap().contains_key(key)
+    }
+    pub fn set<K: Into<String>, V: ToString>(&self, key: K, value: V) -> io::Result<()> {
+        self.inner
+            .data
+            .lock()
+            .unwrap()
+            .insert(key.into(), value.to_string());
+        self.save()
+    }
+    pub fn remove(&self, key: &str) -> io::Result<()> {
+        if self.inner.data.lock().unwrap().remove(key).is_none() {
+            return Ok(());
+        }
+        self.save()
+    }
+    // Moves the data to a new ID. Call it when the privilege level changes,
+    // e.g. on login, so that an ID planted before can't be used afterwards.
+    pub fn regenerate(&self) -> io::Result<()> {
+        let old = std::mem::replace(&mut *self.inner.id.lock().unwrap(), new_id());
+        let mut stored = self.inner.stored.lock().unwrap();
+        if *stored {
+            self.inner.options.store.remove(&old)?;
+            *stored = false;
+        }
+        drop(stored);
+        self.save()
+    }
+    // Forgets the session, on the server and in the client.
+    pub fn destroy(&self) -> io::Result<()> {
+        let options = &self.inner.options;
+        self.inner.data.lock().unwrap().clear();
+        let mut stored = self.inner.stored.lock().unwrap();
+        if *stored {
+            options.store.remove(&self.id())?;
+            *stored = false;
+        }
+        let cookie = Cookie::removal(options.cookie_name.as_str()).path("/");
+        self.set_cookie(cookie);
+        Ok(())
+    }
+    fn save(&self) -> io::Result<()> {
+        let options = &self.inner.options;
+        let id = self.id();
+        options
+            .store
+            .save(&id, &self.inner.data.lock().unwrap(), options.ttl)?;
+        // The store restarted the expiry, the cookie's Max-Age follows.
+        let cookie = Cookie::new(options.cookie_name.as_str(), id)
+            .path("/")
+            .max_age(options.ttl)
+            .http_only(true)
+            .secure(options.secure)
+            .same_site(SameSite::Lax);
+        self.set_cookie(cookie);
+        *self.inner.stored.lock().unwrap() = true;
+        Ok(())
+    }
+    fn set_cookie(&self, cookie: Cookie) {
+        let name = &self.inner.options.cookie_name;
+        let mut header = self.inner.header.lock().unwrap();
+        // Only the last word about the session cookie counts.
+        let others: Vec<String> = header
+            .get_all(header::SET_COOKIE)
+            .filter(|c| !c.starts_with(&format!("{}=", name)))
+            .map(str::to_owned)
+            .collect();
+        header.remove(header::SET_COOKIE);
+        for c in others {
+            header.append(header::SET_COOKIE, c);
+        }
+        header.append(header::SET_COOKIE, cookie.to_string());
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("data", &self.inner.data.lock().unwrap())
+            .finish()
+    }
+}
+
+// 256 random bits, base64url encoded.
+fn new_id() -> String {
+    let mut bytes = [0u8; 32];
+    crypto::random_bytes(&mut bytes);
+    crypto::base64_encode(&bytes)
+}
+
+// IDs end up in file names, anything but our own format is ignored.
+fn is_valid_id(id: &str) -> bool {
+    id.len() == ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
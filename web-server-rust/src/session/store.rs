@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::url;
+
+pub type SessionData = HashMap<String, String>;
+
+// Where sessions live between requests. `save` is called on every change
+// and restarts the expiry.
+pub trait SessionStore: Send + Sync {
+    fn load(&self, id: &str) -> io::Result<Option<SessionData>>;
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> io::Result<()>;
+    fn remove(&self, id: &str) -> io::Result<()>;
+}
+
+// Sessions kept in the process, lost on restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, (SessionData, Instant)>>,
+    saves: AtomicUsize,
+}
+
+impl MemoryStore {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> io::Result<Option<SessionData>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(id) {
+            Some((data, expires)) if *expires > Instant::now() => Ok(Some(data.clone())),
+            Some(_) => {
+                sessions.remove(id);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> io::Result<()> {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        // Abandoned sessions are never loaded again, sweep them now and then.
+        if self
+            .saves
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(64)
+        {
+            sessions.retain(|_, (_, expires)| *expires > now);
+        }
+        sessions.insert(id.to_owned(), (data.clone(), now + ttl));
+        Ok(())
+    }
+    fn remove(&self, id: &str) -> io::Result<()> {
+        self.sessions.lock().unwrap().remove(id);
+        Ok(())
+    }
+}
+
+// One file per session in a directory, so sessions survive restarts.
+// Ex:
+//     1767225600
+//     user=42
+//     cart=a%2Cb
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    // The file names are session IDs, only the owner may list them. An
+    // existing directory keeps its permissions.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&dir)?;
+        Ok(Self { dir })
+    }
+    // Expired files are only removed when their session is requested, call
+    // this now and then to clean up the rest. Files not named like a session
+    // ID or not written by `save` are left alone.
+    pub fn purge_expired(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if let Some(id) = entry.file_name().to_str()
+                && super::is_valid_id(id)
+            {
+                self.load(id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Ex: "1767225600\nuser=42\n" -> (1767225600, {"user": "42"})
+fn parse(content: &str) -> Option<(u64, SessionData)> {
+    let mut lines = content.lines();
+    let expires = lines.next()?.parse().ok()?;
+    let data = lines
+        .map(|line| {
+            let (k, v) = line.split_once('=')?;
+            Some((url::decode(k, false), url::decode(v, false)))
+        })
+        .collect::<Option<_>>()?;
+    Some((expires, data))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl SessionStore for FileStore {
+    fn load(&self, id: &str) -> io::Result<Option<SessionData>> {
+        let content = match fs::read_to_string(self.dir.join(id)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        // Not ours, so not ours to delete either.
+        let Some((expires, data)) = parse(&content) else {
+            return Ok(None);
+        };
+        if expires <= unix_now() {
+            self.remove(id)?;
+            return Ok(None);
+        }
+        Ok(Some(data))
+    }
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> io::Result<()> {
+        let mut content = format!("{}\n", unix_now() + ttl.as_secs());
+        for (key, value) in data {
+            content.push_str(&format!("{}={}\n", url::encode(key), url::encode(value)));
+        }
+        // Readers never see a half written file. The process ID keeps other
+        // processes sharing the directory from writing the same one.
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let tmp = self.dir.join(format!(
+            "{}.{}.{}.tmp",
+            id,
+            process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut options = fs::File::options();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let written = options
+            .open(&tmp)
+            .and_then(|mut file| file.write_all(content.as_bytes()));
+        if let Err(err) = written {
+            let _ = fs::remove_file(&tmp);
+            return Err(err);
+        }
+        fs::rename(tmp, self.dir.join(id))
+    }
+    fn remove(&self, id: &str) -> io::Result<()> {
+        match fs::remove_file(self.dir.join(id)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
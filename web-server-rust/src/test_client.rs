@@ -0,0 +1,212 @@
+use std::{
+    io::{self, BufRead, Cursor, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    App,
+    body::{BodyReader, BodyState},
+    header::{self, HeaderMap},
+    method::Method,
+    server::{Duplex, Reader, ServerState, Writer, serve_connection},
+};
+
+// Runs requests through an `App` in the calling thread, over in-memory
+// buffers, the same way a server would handle a connection.
+// Ex:
+//     let client = TestClient::new(app);
+//     let res = client.get("/users/7").send()?;
+//     assert_eq!(res.status(), 200);
+pub struct TestClient {
+    app: Arc<App<Reader, Writer>>,
+    state: ServerState,
+}
+
+impl TestClient {
+    pub fn new(app: App<Reader, Writer>) -> Self {
+        Self {
+            app: Arc::new(app),
+            state: ServerState::new(Box::new(|| {})),
+        }
+    }
+    #[inline]
+    pub fn request(&self, method: Method, target: &str) -> TestRequest<'_> {
+        TestRequest {
+            client: self,
+            method,
+            target: target.to_owned(),
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        }
+    }
+    #[inline]
+    pub fn get(&self, target: &str) -> TestRequest<'_> {
+        self.request(Method::Get, target)
+    }
+    #[inline]
+    pub fn head(&self, target: &str) -> TestRequest<'_> {
+        self.request(Method::Head, target)
+    }
+    #[inline]
+    pub fn post(&self, target: &str) -> TestRequest<'_> {
+        self.request(Method::Post, target)
+    }
+    #[inline]
+    pub fn put(&self, target: &str) -> TestRequest<'_> {
+        self.request(Method::Put, target)
+    }
+    #[inline]
+    pub fn patch(&self, target: &str) -> TestRequest<'_> {
+        self.request(Method::Patch, target)
+    }
+    #[inline]
+    pub fn delete(&self, target: &str) -> TestRequest<'_> {
+        self.request(Method::Delete, target)
+    }
+    #[inline]
+    pub fn options(&self, target: &str) -> TestRequest<'_> {
+        self.request(Method::Options, target)
+    }
+    // Feeds `input` as is, which may hold several or malformed requests,
+    // and returns everything written back.
+    pub fn send_raw(&self, input: &[u8]) -> io::Result<Vec<u8>> {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let conn = Duplex::new(Cursor::new(input.to_vec()), Output(output.clone()));
+        serve_connection(self.app.clone(), &self.state, 0, Box::new(conn))?;
+        Ok(std::mem::take(&mut *output.lock().unwrap()))
+    }
+}
+
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct TestRequest<'a> {
+    client: &'a TestClient,
+    method: Method,
+    target: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl TestRequest<'_> {
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.append(key, value);
+        self
+    }
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+    pub fn send(self) -> io::Result<TestResponse> {
+        let has = |name: &str| self.headers.contains_key(name);
+        let mut input = format!("{} {} HTTP/1.1\r\n", self.method, self.target);
+        if !has(header::HOST) {
+            input.push_str("Host: localhost\r\n");
+        }
+        if !self.body.is_empty() && !has(header::CONTENT_LENGTH) && !has(header::TRANSFER_ENCODING)
+        {
+            input.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        for (key, value) in self.headers.iter() {
+            input.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        input.push_str("\r\n");
+        let mut input = input.into_bytes();
+        input.extend_from_slice(&self.body);
+        let output = self.client.send_raw(&input)?;
+        TestResponse::parse(&output, self.method == Method::Head)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    status: u32,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    fn parse(raw: &[u8], head: bool) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+        let mut r = Cursor::new(raw);
+        let mut line = String::new();
+        r.read_line(&mut line)?;
+        let status = line
+            .strip_prefix("HTTP/1.1 ")
+            .and_then(|rest| rest.get(..3))
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| invalid("invalid status line"))?;
+        let mut headers = HeaderMap::new();
+        loop {
+            line.clear();
+            if r.read_line(&mut line)? == 0 {
+                return Err(invalid("unexpected end of response head"));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                break;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid("invalid header"))?;
+            headers.append(key, value.trim());
+        }
+        let mut res = Self {
+            status,
+            headers,
+            body: Vec::new(),
+        };
+        if head || matches!(status, 100..=199 | 204 | 304) {
+            return Ok(res);
+        }
+        let len = res.header(header::CONTENT_LENGTH).map(str::to_owned);
+        let te = res.header(header::TRANSFER_ENCODING).map(str::to_owned);
+        match (len, te) {
+            // Close delimited, the rest is the body.
+            (None, None) => {
+                r.read_to_end(&mut res.body)?;
+            }
+            (len, te) => {
+                let mut state = BodyState::new(len.as_deref(), te.as_deref());
+                state.set_limit(usize::MAX);
+                let reader = Mutex::new(r);
+                let state = Mutex::new(state);
+                BodyReader {
+                    reader: &reader,
+                    state: &state,
+                }
+                .read_to_end(&mut res.body)?;
+            }
+        }
+        Ok(res)
+    }
+    #[inline]
+    pub fn status(&self) -> u32 {
+        self.status
+    }
+    #[inline]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+    #[inline]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+    #[inline]
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+    #[inline]
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
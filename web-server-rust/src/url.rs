@@ -0,0 +1,125 @@
+use crate::error::HttpError;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UrlEncoded(Vec<(String, String)>);
+
+impl UrlEncoded {
+    // Ex: a=1&b=hello+world&b=%21
+    pub fn parse(input: &str) -> Self {
+        Self::parse_limited(input, usize::MAX).unwrap()
+    }
+    // `None` when there are more than `max` fields, checked before decoding.
+    pub(crate) fn parse_limited(input: &str, max: usize) -> Option<Self> {
+        let pairs = input.split('&').filter(|pair| !pair.is_empty());
+        if pairs.clone().count() > max {
+            return None;
+        }
+        let pairs = pairs
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(key, true), decode(value, true))
+            })
+            .collect();
+        Some(Self(pairs))
+    }
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+    #[inline]
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.0.iter().any(|(key, _)| key == name)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// Decodes `%XX` escapes, malformed escapes are kept as they are.
+// With `plus_as_space` a `+` is decoded to ` ` (query strings and forms).
+pub fn decode(input: &str, plus_as_space: bool) -> String {
+    let mut out = Vec::with_capacity(input.len());
+    decode_into(input.as_bytes(), plus_as_space, &mut out);
+    String::from_utf8(out)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+}
+
+fn decode_into(bytes: &[u8], plus_as_space: bool, out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(h), Some(l)) => {
+                    out.push(h << 4 | l);
+                    i += 3;
+                    continue;
+                }
+                _ => out.push(b'%'),
+            },
+            b'+' if plus_as_space => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+}
+
+// Decodes a request path segment by segment. An encoded `/` would turn one
+// segment into two after routing saw one, and a path that is not UTF-8
+// can't be compared to anything, both are refused.
+// Ex: "/a%20b/c" -> "/a b/c"
+pub(crate) fn decode_path(path: &str) -> Result<String, HttpError> {
+    let mut out = Vec::with_capacity(path.len());
+    for (i, segment) in path.split('/').enumerate() {
+        if i > 0 {
+            out.push(b'/');
+        }
+        let start = out.len();
+        decode_into(segment.as_bytes(), false, &mut out);
+        if out[start..].contains(&b'/') {
+            return Err(HttpError::BadRequest);
+        }
+    }
+    String::from_utf8(out).map_err(|_| HttpError::BadRequest)
+}
+
+// Percent-encodes everything but unreserved characters.
+// Ex: "a b/c" -> "a%20b%2Fc"
+pub(crate) fn encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for b in input.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b as char)
+            }
+            b => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+#[inline]
+fn hex(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
@@ -0,0 +1,959 @@
+use std::{
+    io,
+    time::{Duration, SystemTime},
+};
+
+use web_server::{
+    App, Cookie, CookieJar, FileStore, Json, MemoryStore, Method, SameSite, ServeDir, SessionStore,
+    Sessions, Spooled, Status, TestClient, header,
+};
+
+fn client() -> TestClient {
+    let app = App::new();
+    app.use_middleware(|req, res| {
+        if req.path() == "/private" {
+            res.status(Status::Unauthorized);
+            res.send("no")?;
+            return Ok(None);
+        }
+        res.insert_header("X-Middleware", "yes")?;
+        Ok(Some((req, res)))
+    });
+    app.get("/users/:id", |req, res| {
+        res.send(format!("user {}", req.param("id").unwrap()))
+    });
+    app.get("/users/new", |_, res| res.send("new user form"));
+    app.get("/keep-open", |_, res| {
+        res.insert_header(header::CONNECTION, "keep-alive")?;
+        res.send("")
+    });
+    app.delete("/users/:id", |req, res| {
+        res.send(format!("deleted {}", req.param("id").unwrap()))
+    });
+    app.get("/headers", |req, res| {
+        let accept: Vec<_> = req.get_all(header::ACCEPT).collect();
+        res.append_header(header::VARY, "Accept")?;
+        res.append_header(header::VARY, "Origin")?;
+        res.send(format!(
+            "{} {}",
+            req.get("user-agent").unwrap_or("-"),
+            accept.join(";")
+        ))
+    });
+    app.get("/inject", |_, res| {
+        res.insert_header("X-Name", "a\r\nSet-Cookie: evil=1")?;
+        res.send("unreachable")
+    });
+    app.get("/cookies", |req, res| {
+        res.set_cookie(
+            Cookie::new("sid", "abc")
+                .path("/")
+                .max_age(Duration::from_secs(3600))
+                .expires(SystemTime::UNIX_EPOCH + Duration::from_secs(784111777))
+                .secure(true)
+                .http_only(true)
+                .same_site(SameSite::Lax),
+        )?;
+        res.remove_cookie("old")?;
+        let all: Vec<_> = req
+            .cookies()
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect();
+        res.send(format!(
+            "{} {}",
+            req.cookie("b").unwrap_or("-"),
+            all.join(",")
+        ))
+    });
+    app.get("/bad-cookie", |_, res| {
+        res.set_cookie(Cookie::new("a", "x; Domain=evil"))?;
+        res.send("unreachable")
+    });
+    app.post("/echo", |req, res| res.send(req.body_string()?));
+    app.get("/stream", |_, res| {
+        let mut w = res.stream()?;
+        io::Write::write_all(&mut w, b"one ")?;
+        io::Write::flush(&mut w)?;
+        io::Write::write_all(&mut w, b"two")?;
+        w.finish()
+    });
+    TestClient::new(app)
+}
+
+#[test]
+fn routes_with_params() {
+    let res = client().get("/users/7").send().unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.text(), "user 7");
+    assert_eq!(res.header("x-middleware"), Some("yes"));
+}
+
+#[test]
+fn middleware_can_answer() {
+    let res = client().get("/private").send().unwrap();
+    assert_eq!(res.status(), 401);
+    assert_eq!(res.text(), "no");
+}
+
+#[test]
+fn request_body() {
+    let res = client().post("/echo").body("hello").send().unwrap();
+    assert_eq!(res.text(), "hello");
+}
+
+#[test]
+fn not_found_and_method_not_allowed() {
+    let client = client();
+    assert_eq!(client.get("/nothing").send().unwrap().status(), 404);
+    let res = client.request(Method::Put, "/echo").send().unwrap();
+    assert_eq!(res.status(), 405);
+    assert_eq!(res.header("Allow"), Some("POST"));
+}
+
+#[test]
+fn static_route_does_not_hide_param_route_of_other_method() {
+    let client = client();
+    let res = client.get("/users/new").send().unwrap();
+    assert_eq!(res.text(), "new user form");
+    let res = client.request(Method::Delete, "/users/new").send().unwrap();
+    assert_eq!(res.text(), "deleted new");
+    let res = client.request(Method::Put, "/users/new").send().unwrap();
+    assert_eq!(res.status(), 405);
+    assert_eq!(res.header("Allow"), Some("DELETE, GET, HEAD"));
+}
+
+#[test]
+fn head_has_no_body() {
+    let res = client().head("/users/1").send().unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.header("Content-Length"), Some("6"));
+    assert!(res.body().is_empty());
+}
+
+#[test]
+fn chunked_response_is_decoded() {
+    let res = client().get("/stream").send().unwrap();
+    assert_eq!(res.header("Transfer-Encoding"), Some("chunked"));
+    assert_eq!(res.text(), "one two");
+}
+
+#[test]
+fn malformed_request() {
+    let raw = client().send_raw(b"GET / HTTP/9.9\r\n\r\n").unwrap();
+    assert!(raw.starts_with(b"HTTP/1.1 505 "));
+}
+
+#[test]
+fn malformed_content_length() {
+    let client = client();
+    for length in [
+        "Content-Length: 5\r\nContent-Length: 6",
+        "Content-Length: 5, 5",
+        "Content-Length: +5",
+        "Content-Length: 0x5",
+        "Content-Length: 99999999999999999999",
+    ] {
+        let raw = client
+            .send_raw(format!("POST /echo HTTP/1.1\r\n{}\r\n\r\nhello", length).as_bytes())
+            .unwrap();
+        assert!(raw.starts_with(b"HTTP/1.1 400 "), "{}", length);
+        assert!(raw.windows(19).any(|w| w == b"Connection: close\r\n"));
+    }
+    let raw = client
+        .send_raw(b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\nhello")
+        .unwrap();
+    assert!(raw.starts_with(b"HTTP/1.1 200 "));
+    assert!(raw.ends_with(b"hello"));
+}
+
+#[test]
+fn path_segments_are_decoded_one_by_one() {
+    let client = client();
+    let res = client.get("/users/caf%C3%A9").send().unwrap();
+    assert_eq!(res.text(), "user café");
+    // `%2F` must not split a segment in two, invalid UTF-8 is not guessed.
+    for path in ["/users/a%2Fb", "/users/%FF"] {
+        assert_eq!(client.get(path).send().unwrap().status(), 400);
+    }
+}
+
+// Ex: "HTTP/1.1 200 OK\r\n..." twice -> two heads with their bodies
+fn responses(raw: &[u8]) -> Vec<String> {
+    let raw = String::from_utf8_lossy(raw);
+    raw.split("HTTP/1.1 ").skip(1).map(str::to_owned).collect()
+}
+
+#[test]
+fn connections_are_reused() {
+    let client = client();
+    // The unread body of the first request is skipped.
+    let raw = client
+        .send_raw(
+            b"DELETE /users/1 HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello\
+              GET /users/2 HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+    let res = responses(&raw);
+    assert_eq!(res.len(), 2);
+    assert!(res[0].ends_with("deleted 1"));
+    assert!(!res[0].contains("Connection:"));
+    assert!(res[1].ends_with("user 2"));
+
+    let raw = client
+        .send_raw(
+            b"GET /users/1 HTTP/1.1\r\nConnection: close\r\n\r\n\
+              GET /users/2 HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+    let res = responses(&raw);
+    assert_eq!(res.len(), 1);
+    assert!(res[0].contains("Connection: close\r\n"));
+}
+
+#[test]
+fn http_1_0_closes_unless_asked() {
+    let client = client();
+    let raw = client
+        .send_raw(b"GET /users/1 HTTP/1.0\r\n\r\nGET /users/2 HTTP/1.0\r\n\r\n")
+        .unwrap();
+    let res = responses(&raw);
+    assert_eq!(res.len(), 1);
+    assert!(res[0].contains("Connection: close\r\n"));
+
+    let raw = client
+        .send_raw(
+            b"GET /users/1 HTTP/1.0\r\nConnection: keep-alive\r\n\r\n\
+              GET /users/2 HTTP/1.0\r\n\r\n",
+        )
+        .unwrap();
+    let res = responses(&raw);
+    assert_eq!(res.len(), 2);
+    assert!(res[0].contains("Connection: keep-alive\r\n"));
+    assert!(res[1].contains("Connection: close\r\n"));
+}
+
+#[test]
+fn handler_cannot_keep_a_closing_connection_open() {
+    let raw = client()
+        .send_raw(
+            b"GET /keep-open HTTP/1.1\r\nConnection: close\r\n\r\n\
+              GET /users/2 HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+    let res = responses(&raw);
+    assert_eq!(res.len(), 1);
+    assert!(res[0].contains("Connection: close\r\n"));
+    assert!(!res[0].contains("keep-alive"));
+}
+
+#[test]
+fn ambiguous_body_framing_is_rejected() {
+    let raw = client()
+        .send_raw(
+            b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n\
+              0\r\n\r\nGET /users/7 HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+    assert!(raw.starts_with(b"HTTP/1.1 400 "));
+    // The connection is closed, the smuggled request is never answered.
+    assert_eq!(raw.windows(9).filter(|w| w == b"HTTP/1.1 ").count(), 1);
+}
+
+#[test]
+fn chunked_request_bodies() {
+    let post = |codings: &str, body: &str| {
+        let raw = client()
+            .send_raw(
+                format!(
+                    "POST /echo HTTP/1.1\r\nTransfer-Encoding: {}\r\n\r\n{}",
+                    codings, body
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+        String::from_utf8(raw).unwrap()
+    };
+    let res = post(
+        "chunked",
+        "3;ext=1\r\nhel\r\n2\r\nlo\r\n0\r\nX-Sum: 1\r\n\r\n",
+    );
+    assert!(res.starts_with("HTTP/1.1 200 "), "{}", res);
+    assert!(res.ends_with("\r\n\r\nhello"));
+    // Only CRLF ends a line.
+    assert!(post("chunked", "5\nhello\r\n0\r\n\r\n").starts_with("HTTP/1.1 400 "));
+    assert!(post("chunked", "5\r\nhello\r\n0\r\n\n").starts_with("HTTP/1.1 400 "));
+    // Codings that can't be decoded are refused, not passed on encoded.
+    assert!(post("gzip, chunked", "0\r\n\r\n").starts_with("HTTP/1.1 501 "));
+    assert!(post("chunked, gzip", "0\r\n\r\n").starts_with("HTTP/1.1 400 "));
+    // Trailers are capped like headers.
+    let trailers = "X-Pad: 0123456789abcdef\r\n".repeat(3000);
+    let res = post("chunked", &format!("0\r\n{}\r\n", trailers));
+    assert!(res.starts_with("HTTP/1.1 431 "), "{}", res);
+}
+
+#[test]
+fn headers_are_case_insensitive_and_repeatable() {
+    let res = client()
+        .get("/headers")
+        .header("USER-AGENT", "test")
+        .header("Accept", "text/html")
+        .header("accept", "*/*")
+        .send()
+        .unwrap();
+    assert_eq!(res.text(), "test text/html;*/*");
+    let vary: Vec<_> = res.headers().get_all("vary").collect();
+    assert_eq!(vary, ["Accept", "Origin"]);
+}
+
+#[test]
+fn header_injection_is_rejected() {
+    let res = client().get("/inject").send().unwrap();
+    assert_eq!(res.status(), 500);
+    assert!(res.header("Set-Cookie").is_none());
+}
+
+#[test]
+fn cookies() {
+    let res = client()
+        .get("/cookies")
+        .header("Cookie", "a=1; b=\"two\"")
+        .send()
+        .unwrap();
+    assert_eq!(res.text(), "two a=1,b=two");
+    let set: Vec<_> = res.headers().get_all("set-cookie").collect();
+    assert_eq!(
+        set,
+        [
+            "sid=abc; Path=/; Max-Age=3600; Expires=Sun, 06 Nov 1994 08:49:37 GMT; Secure; HttpOnly; SameSite=Lax",
+            "old=; Max-Age=0; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+        ]
+    );
+    assert_eq!(client().get("/bad-cookie").send().unwrap().status(), 500);
+}
+
+// Signing works with or without the `crypto` feature.
+#[test]
+fn signed_cookies() {
+    let app = App::new();
+    let jar = CookieJar::new(&[1; 32]);
+    let writer = jar.clone();
+    app.get("/set", move |_, res| {
+        writer.set_signed(&res, Cookie::new("user", "42"))?;
+        res.send("")
+    });
+    app.get("/get", move |req, res| {
+        res.send(jar.signed(&req, "user").unwrap_or("-").to_owned())
+    });
+    let client = TestClient::new(app);
+    let res = client.get("/set").send().unwrap();
+    let cookie = res.header("Set-Cookie").unwrap().split(';').next().unwrap();
+    assert!(cookie.starts_with("user=42."));
+    let get = |cookie: &str| {
+        client
+            .get("/get")
+            .header("Cookie", cookie)
+            .send()
+            .unwrap()
+            .text()
+    };
+    assert_eq!(get(cookie), "42");
+    assert_eq!(get(&cookie.replace("user=42.", "user=43.")), "-");
+    assert_eq!(get(&cookie.replace("user=", "other=")), "-");
+}
+
+#[cfg(feature = "crypto")]
+fn jar_client(jar: CookieJar) -> TestClient {
+    let app = App::new();
+    let writer = jar.clone();
+    app.get("/set", move |_, res| {
+        writer.set_signed(&res, Cookie::new("user", "42"))?;
+        writer.set_private(&res, Cookie::new("secret", "a b;c"))?;
+        res.send("")
+    });
+    app.get("/get", move |req, res| {
+        let user = jar.signed(&req, "user").unwrap_or("-").to_owned();
+        let secret = jar.private(&req, "secret").unwrap_or("-".to_owned());
+        res.send(format!("{} {}", user, secret))
+    });
+    TestClient::new(app)
+}
+
+#[cfg(feature = "crypto")]
+fn set_cookies(client: &TestClient) -> String {
+    let res = client.get("/set").send().unwrap();
+    res.headers()
+        .get_all("Set-Cookie")
+        .map(|c| c.split(';').next().unwrap())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn signed_and_private_cookies() {
+    let client = jar_client(CookieJar::new(&[1; 32]));
+    let cookies = set_cookies(&client);
+    assert!(cookies.starts_with("user=42."));
+    assert!(!cookies.contains("a b"));
+    let get = |cookies: &str| {
+        client
+            .get("/get")
+            .header("Cookie", cookies)
+            .send()
+            .unwrap()
+            .text()
+    };
+    assert_eq!(get(&cookies), "42 a b;c");
+    assert_eq!(get(&cookies.replace("user=42.", "user=43.")), "- a b;c");
+    let (user, secret) = cookies.split_once("; ").unwrap();
+    let mut tampered = secret.to_owned().into_bytes();
+    let i = tampered.len() / 2;
+    tampered[i] = if tampered[i] == b'A' { b'B' } else { b'A' };
+    let tampered = format!("{}; {}", user, String::from_utf8(tampered).unwrap());
+    assert_eq!(get(&tampered), "42 -");
+    // A cookie moved to another name does not verify.
+    assert_eq!(
+        get(&cookies
+            .replace("user=", "other=")
+            .replace("secret=", "user=")),
+        "- -"
+    );
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn cookie_key_rotation() {
+    let old = jar_client(CookieJar::new(&[1; 32]));
+    let cookies = set_cookies(&old);
+    let rotated = jar_client(CookieJar::new(&[2; 32]).old_key(&[1; 32]));
+    let res = rotated
+        .get("/get")
+        .header("Cookie", &cookies)
+        .send()
+        .unwrap();
+    assert_eq!(res.text(), "42 a b;c");
+    let dropped = jar_client(CookieJar::new(&[2; 32]));
+    let res = dropped
+        .get("/get")
+        .header("Cookie", &cookies)
+        .send()
+        .unwrap();
+    assert_eq!(res.text(), "- -");
+}
+
+fn session_client<S: SessionStore + 'static>(store: S) -> TestClient {
+    let app = App::new();
+    app.use_middleware(Sessions::new(store).middleware());
+    app.get("/visit", |req, res| {
+        let session = req.session().unwrap();
+        let visits = session.get::<u32>("visits").unwrap_or(0) + 1;
+        session.set("visits", visits)?;
+        res.send(visits)
+    });
+    app.get("/peek", |req, res| {
+        let visits = req.session().unwrap().get::<u32>("visits");
+        res.send(visits.map_or("-".to_owned(), |v| v.to_string()))
+    });
+    app.post("/login", |req, res| {
+        let session = req.session().unwrap();
+        session.regenerate()?;
+        session.set("user", "alice = admin\n")?;
+        res.send(session.get::<String>("user").unwrap())
+    });
+    app.post("/logout", |req, res| {
+        req.session().unwrap().destroy()?;
+        res.send("")
+    });
+    TestClient::new(app)
+}
+
+fn sid(res: &web_server::TestResponse) -> Option<String> {
+    res.headers()
+        .get_all("Set-Cookie")
+        .find_map(|c| c.strip_prefix("sid="))
+        .map(|c| c.split(';').next().unwrap().to_owned())
+}
+
+fn check_sessions(client: TestClient) {
+    let visit = |cookie: &str| {
+        client
+            .get("/visit")
+            .header("Cookie", cookie)
+            .send()
+            .unwrap()
+    };
+    // Reading alone creates nothing.
+    let res = client.get("/peek").send().unwrap();
+    assert_eq!((res.text().as_str(), sid(&res)), ("-", None));
+    let res = client.get("/visit").send().unwrap();
+    let id = sid(&res).unwrap();
+    let cookie = format!("sid={}", id);
+    let res = visit(&cookie);
+    assert_eq!(res.text(), "2");
+    // Saving restarts the expiry, in the store and in the cookie.
+    assert_eq!(sid(&res).unwrap(), id);
+    let set_cookie = res.headers().get_all("Set-Cookie").next().unwrap();
+    assert!(set_cookie.contains("; Max-Age=86400"), "{}", set_cookie);
+    // Unknown IDs are replaced, not adopted.
+    let planted = format!("sid={}", "A".repeat(43));
+    let res = visit(&planted);
+    assert_eq!(res.text(), "1");
+    assert_ne!(sid(&res).unwrap(), "A".repeat(43));
+    let res = client
+        .post("/login")
+        .header("Cookie", &cookie)
+        .send()
+        .unwrap();
+    assert_eq!(res.text(), "alice = admin\n");
+    let new_id = sid(&res).unwrap();
+    assert_ne!(new_id, id);
+    assert_eq!(visit(&cookie).text(), "1");
+    let new_cookie = format!("sid={}", new_id);
+    assert_eq!(visit(&new_cookie).text(), "3");
+    let res = client
+        .post("/logout")
+        .header("Cookie", &new_cookie)
+        .send()
+        .unwrap();
+    assert_eq!(sid(&res).as_deref(), Some(""));
+    let res = client
+        .get("/peek")
+        .header("Cookie", &new_cookie)
+        .send()
+        .unwrap();
+    assert_eq!(res.text(), "-");
+}
+
+#[test]
+fn memory_sessions() {
+    check_sessions(session_client(MemoryStore::new()));
+}
+
+#[test]
+fn file_sessions() {
+    let dir = std::env::temp_dir().join(format!("web-server-sessions-{}", std::process::id()));
+    check_sessions(session_client(FileStore::new(&dir).unwrap()));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().flatten().collect();
+        assert!(!files.is_empty());
+        assert!(files.iter().all(|file| mode(&file.path()) == 0o600));
+    }
+    // Only expired sessions are purged, never files the store didn't write.
+    let expired = "B".repeat(43);
+    let corrupt = "C".repeat(43);
+    std::fs::write(dir.join(&expired), "1\nuser=42\n").unwrap();
+    std::fs::write(dir.join(&corrupt), "not a session").unwrap();
+    std::fs::write(dir.join("notes.txt"), "1\n").unwrap();
+    FileStore::new(&dir).unwrap().purge_expired().unwrap();
+    assert!(!dir.join(expired).exists());
+    assert!(dir.join(corrupt).exists());
+    assert!(dir.join("notes.txt").exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn json_bodies() {
+    let app = App::new();
+    app.post("/users", |req, res| {
+        let body = req.json()?;
+        let mut user = Json::object([("id", Json::from(7))]);
+        user.insert("name", body["name"].as_str().unwrap_or("anonymous"));
+        res.status(Status::Created);
+        res.json(&user)
+    });
+    let client = TestClient::new(app);
+    let res = client
+        .post("/users")
+        .body(r#"{"name": "Ann \"A\""}"#)
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 201);
+    assert_eq!(res.header("Content-Type"), Some("application/json"));
+    assert_eq!(res.text(), r#"{"id":7,"name":"Ann \"A\""}"#);
+    let res = client.post("/users").body("{name: 1}").send().unwrap();
+    assert_eq!(res.status(), 400);
+}
+
+#[test]
+fn url_encoded_forms() {
+    let app = App::new();
+    app.form_limit(64);
+    app.form_fields(3);
+    app.post("/login", |req, res| {
+        let form = req.form()?;
+        res.send(format!(
+            "{} {:?}",
+            form.get("user").unwrap_or(""),
+            form.get_all("role")
+        ))
+    });
+    let client = TestClient::new(app);
+    let form = "application/x-www-form-urlencoded; charset=UTF-8";
+    let res = client
+        .post("/login")
+        .header("Content-Type", form)
+        .body("user=Ann+Lee&role=a&role=b%26c")
+        .send()
+        .unwrap();
+    assert_eq!(res.text(), r#"Ann Lee ["a", "b&c"]"#);
+    let res = client
+        .post("/login")
+        .header("Content-Type", "text/plain")
+        .body("user=ann")
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 415);
+    let res = client
+        .post("/login")
+        .header("Content-Type", form)
+        .body("a=1&b=2&c=3&d=4")
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 413);
+    let res = client
+        .post("/login")
+        .header("Content-Type", form)
+        .body(format!("user={}", "a".repeat(64)))
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 413);
+}
+
+fn multipart_body(boundary: &str, parts: &[(&str, &str)]) -> Vec<u8> {
+    let mut body = b"preamble\r\n".to_vec();
+    for (headers, data) in parts {
+        body.extend(format!("--{}\r\n{}\r\n\r\n{}\r\n", boundary, headers, data).bytes());
+    }
+    body.extend(format!("--{}--\r\nepilogue", boundary).bytes());
+    body
+}
+
+#[test]
+fn multipart_uploads() {
+    let dir = std::env::temp_dir().join(format!("web-server-uploads-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let app = App::new();
+    app.part_memory(16);
+    app.part_limit(30_000);
+    app.upload_dir(dir.join("spool"));
+    std::fs::create_dir_all(dir.join("spool")).unwrap();
+    let uploads = dir.clone();
+    app.post("/upload", move |req, res| {
+        let mut form = req.multipart()?;
+        let mut out = Vec::new();
+        while let Some(mut part) = form.next_part()? {
+            match part.filename().map(str::to_owned) {
+                Some(filename) => {
+                    let content_type = part.content_type().unwrap_or("").to_owned();
+                    let spooled = part.spool()?;
+                    let Spooled::File(file) = &spooled else {
+                        panic!("{} was kept in memory", filename);
+                    };
+                    assert!(file.path().starts_with(uploads.join("spool")));
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::PermissionsExt;
+                        let mode = std::fs::metadata(file.path()).unwrap().permissions().mode();
+                        assert_eq!(mode & 0o777, 0o600);
+                    }
+                    out.push(format!("{} {} {}", filename, content_type, spooled.len()));
+                    spooled.persist(uploads.join("upload"))?;
+                }
+                None => {
+                    let name = part.name().to_owned();
+                    out.push(format!("{}={}", name, part.text()?));
+                }
+            }
+        }
+        res.send(out.join("\n"))
+    });
+    let client = TestClient::new(app);
+    let content_type = "multipart/form-data; boundary=\"x-boundary\"";
+    let file = "0123456789\r\n-".repeat(2000);
+    let body = multipart_body(
+        "x-boundary",
+        &[
+            ("Content-Disposition: form-data; name=\"title\"", "Hi --x"),
+            (
+                "Content-Disposition: form-data; name=\"doc\"; filename=\"a.txt\"; \
+                 filename*=UTF-8''na%C3%AFve.txt\r\nContent-Type: text/plain",
+                &file,
+            ),
+            ("Content-Disposition: form-data; name=\"skipped\"", "x"),
+        ],
+    );
+    let res = client
+        .post("/upload")
+        .header("Content-Type", content_type)
+        .body(body)
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.text(),
+        "title=Hi --x\nnaïve.txt text/plain 26000\nskipped=x"
+    );
+    assert_eq!(std::fs::read_to_string(dir.join("upload")).unwrap(), file);
+
+    let large = multipart_body(
+        "b",
+        &[(
+            "Content-Disposition: form-data; name=\"f\"; filename=\"f\"",
+            &"a".repeat(30_001),
+        )],
+    );
+    let res = client
+        .post("/upload")
+        .header("Content-Type", "multipart/form-data; boundary=b")
+        .body(large)
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 413);
+    let res = client
+        .post("/upload")
+        .header("Content-Type", "multipart/form-data; boundary=b")
+        .body("--b\r\nno headers")
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 400);
+    let res = client.post("/upload").body("x").send().unwrap();
+    assert_eq!(res.status(), 415);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn serve_dir() {
+    let dir = std::env::temp_dir().join(format!("web-server-static-{}", std::process::id()));
+    let public = dir.join("public");
+    std::fs::create_dir_all(public.join("docs")).unwrap();
+    std::fs::create_dir_all(public.join("empty dir")).unwrap();
+    std::fs::write(public.join("app.json"), "{}").unwrap();
+    std::fs::write(public.join("data.bin"), "01").unwrap();
+    std::fs::write(public.join("site.css"), "a {}").unwrap();
+    std::fs::write(public.join(".env"), "SECRET=1").unwrap();
+    std::fs::write(public.join("docs/index.html"), "<h1>Docs</h1>").unwrap();
+    std::fs::write(public.join("empty dir/<b>.txt"), "b").unwrap();
+    std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(dir.join("secret.txt"), public.join("link.txt")).unwrap();
+
+    let app = App::new();
+    app.serve_dir("/static", &public);
+    app.serve_dir_with("/files", ServeDir::new(&public).listing(true));
+    let client = TestClient::new(app);
+
+    let res = client.get("/static/app.json").send().unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.header("Content-Type"), Some("application/json"));
+    assert_eq!(res.text(), "{}");
+    let res = client.get("/static/site.css").send().unwrap();
+    assert_eq!(res.header("Content-Type"), Some("text/css; charset=utf-8"));
+    let res = client.head("/static/data.bin").send().unwrap();
+    assert_eq!(res.header("Content-Type"), Some("application/octet-stream"));
+    assert_eq!(res.header("Content-Length"), Some("2"));
+    assert_eq!(res.text(), "");
+    let res = client.get("/static/docs/").send().unwrap();
+    assert_eq!(res.text(), "<h1>Docs</h1>");
+    let res = client.get("/static/docs").send().unwrap();
+    assert_eq!(res.status(), 301);
+    assert_eq!(res.header("Location"), Some("/static/docs/"));
+    let res = client.post("/static/app.json").send().unwrap();
+    assert_eq!(res.status(), 405);
+    for path in [
+        "/static/../secret.txt",
+        "/static/%2e%2e/secret.txt",
+        "/static/.env",
+        "/static/link.txt",
+        "/static/missing.txt",
+        "/static/",
+    ] {
+        assert_eq!(client.get(path).send().unwrap().status(), 404, "{}", path);
+    }
+    let res = client
+        .get("/static/docs/%2E%2E%2F..%2Fsecret.txt")
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 400);
+
+    let res = client.get("/files/").send().unwrap();
+    let page = res.text();
+    assert!(page.contains(r#"<a href="empty%20dir/">empty dir/</a>"#));
+    assert!(page.contains(r#"<a href="app.json">app.json</a>"#));
+    assert!(!page.contains(".env"));
+    assert!(!page.contains("../"));
+    let page = client.get("/files/empty%20dir/").send().unwrap().text();
+    assert!(page.contains(r#"<a href="%3Cb%3E.txt">&lt;b&gt;.txt</a>"#));
+    assert!(page.contains(r#"<a href="../">"#));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn range_requests() {
+    let path = std::env::temp_dir().join(format!("web-server-range-{}.txt", std::process::id()));
+    std::fs::write(&path, "0123456789").unwrap();
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let app = App::new();
+    let file = path.clone();
+    app.get("/file", move |_, res| res.send_file(&file));
+    let client = TestClient::new(app);
+    let get = |range: &str| client.get("/file").header("Range", range).send().unwrap();
+
+    let res = client.get("/file").send().unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.header("Accept-Ranges"), Some("bytes"));
+    let res = get("bytes=2-4");
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.header("Content-Range"), Some("bytes 2-4/10"));
+    assert_eq!(res.text(), "234");
+    assert_eq!(get("bytes=-3").text(), "789");
+    let res = get("bytes=10-");
+    assert_eq!(res.status(), 416);
+    assert_eq!(res.header("Content-Range"), Some("bytes */10"));
+    assert_eq!(get("bytes=4-2").status(), 200);
+    // Overlapping ranges are sent once, as a single part.
+    let res = get("bytes=5-7, 2-5");
+    assert_eq!(res.header("Content-Range"), Some("bytes 2-7/10"));
+    assert_eq!(res.text(), "234567");
+
+    let res = get("bytes=0-1, 8-");
+    assert_eq!(res.status(), 206);
+    let content_type = res.header("Content-Type").unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .unwrap();
+    assert_eq!(
+        res.text(),
+        format!(
+            "\r\n--{0}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 0-1/10\r\n\r\n01\
+             \r\n--{0}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 8-9/10\r\n\r\n89\
+             \r\n--{0}--\r\n",
+            boundary
+        )
+    );
+
+    let if_range = |value: &str| {
+        client
+            .get("/file")
+            .header("Range", "bytes=0-0")
+            .header("If-Range", value)
+            .send()
+            .unwrap()
+            .status()
+    };
+    assert_eq!(if_range("Sun, 06 Nov 1994 08:49:37 GMT"), 206);
+    assert_eq!(if_range("Sun, 06 Nov 1994 08:49:38 GMT"), 200);
+    assert_eq!(if_range("\"stale\""), 200);
+    assert_eq!(
+        client
+            .head("/file")
+            .header("Range", "bytes=0-0")
+            .send()
+            .unwrap()
+            .status(),
+        200
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn conditional_requests() {
+    let path = std::env::temp_dir().join(format!("web-server-etag-{}.css", std::process::id()));
+    std::fs::write(&path, "a {}").unwrap();
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let app = App::new();
+    let file = path.clone();
+    app.get("/file", move |_, res| res.send_file(&file));
+    app.get("/page", |_, res| res.send("Hello"));
+    app.post("/page", |_, res| res.send("Posted"));
+    let client = TestClient::new(app);
+
+    let res = client.get("/file").send().unwrap();
+    let etag = res.header("ETag").unwrap().to_owned();
+    assert!(etag.starts_with('"'));
+    assert_eq!(
+        res.header("Last-Modified"),
+        Some("Sun, 06 Nov 1994 08:49:37 GMT")
+    );
+    let send =
+        |path: &str, name: &str, value: &str| client.get(path).header(name, value).send().unwrap();
+    let res = send("/file", "If-None-Match", &etag);
+    assert_eq!(res.status(), 304);
+    assert_eq!(res.header("Content-Length"), None);
+    assert_eq!(res.header("ETag"), Some(etag.as_str()));
+    assert_eq!(res.text(), "");
+    assert_eq!(send("/file", "If-None-Match", "\"other\"").status(), 200);
+    let res = send(
+        "/file",
+        "If-Modified-Since",
+        "Sun, 06 Nov 1994 08:49:37 GMT",
+    );
+    assert_eq!(res.status(), 304);
+    let res = send(
+        "/file",
+        "If-Modified-Since",
+        "Sun, 06 Nov 1994 08:49:36 GMT",
+    );
+    assert_eq!(res.status(), 200);
+    assert_eq!(send("/file", "If-Match", "\"other\"").status(), 412);
+    assert_eq!(send("/file", "If-Match", &etag).text(), "a {}");
+    let res = send(
+        "/file",
+        "If-Unmodified-Since",
+        "Sat, 05 Nov 1994 08:49:37 GMT",
+    );
+    assert_eq!(res.status(), 412);
+    let res = client
+        .get("/file")
+        .header("Range", "bytes=0-0")
+        .header("If-Range", &etag)
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 206);
+
+    let res = client.get("/page").send().unwrap();
+    let etag = res.header("ETag").unwrap().to_owned();
+    assert!(etag.starts_with("W/\""));
+    assert_eq!(send("/page", "If-None-Match", &etag).status(), 304);
+    // Weak tags never pass If-Match.
+    assert_eq!(send("/page", "If-Match", &etag).status(), 412);
+    // Sent bodies have no modification date to be newer than the one given.
+    let res = send(
+        "/page",
+        "If-Unmodified-Since",
+        "Sat, 05 Nov 1994 08:49:37 GMT",
+    );
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.text(), "Hello");
+    let res = client
+        .post("/page")
+        .header("If-None-Match", "*")
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.header("ETag"), None);
+    std::fs::remove_file(path).unwrap();
+
+    let app = App::new();
+    app.etag(false);
+    app.get("/page", |_, res| res.send("Hello"));
+    let res = TestClient::new(app).get("/page").send().unwrap();
+    assert_eq!(res.header("ETag"), None);
+}
@@ -0,0 +1,206 @@
+use std::{
+    io::{self, Cursor, Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::{Mutex, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use web_server::{App, Connection, Duplex, Listener, QueuePolicy, Waker};
+
+fn connect(addr: SocketAddr, request: &str) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    stream
+}
+
+fn read_all(mut stream: TcpStream) -> String {
+    let mut out = String::new();
+    stream.read_to_string(&mut out).unwrap();
+    out
+}
+
+fn wait_for(what: &str, f: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !f() {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn full_queue_rejects_with_503() {
+    let app = App::new();
+    app.workers(1);
+    app.queue_size(1);
+    app.queue_policy(QueuePolicy::Reject);
+    let (release, blocked) = mpsc::channel::<()>();
+    let blocked = Mutex::new(blocked);
+    app.get("/slow", move |_, res| {
+        let _ = blocked.lock().unwrap().recv();
+        res.send("done")
+    });
+    let metrics = app.metrics();
+    let server = app.spawn("127.0.0.1:0").unwrap();
+    let addr = server.local_addr();
+    let request = "GET /slow HTTP/1.1\r\nConnection: close\r\n\r\n";
+
+    let first = connect(addr, request);
+    wait_for("a busy worker", || metrics.busy_workers() == 1);
+    let second = connect(addr, request);
+    wait_for("a queued connection", || metrics.queue_depth() == 1);
+    let third = read_all(connect(addr, request));
+    assert!(third.starts_with("HTTP/1.1 503 "), "{}", third);
+    assert!(third.contains("Connection: close\r\n"));
+    assert_eq!(metrics.workers(), 1);
+    assert_eq!(metrics.rejected(), 1);
+
+    release.send(()).unwrap();
+    release.send(()).unwrap();
+    assert!(read_all(first).ends_with("done"));
+    assert!(read_all(second).ends_with("done"));
+    wait_for("an idle worker", || metrics.busy_workers() == 0);
+    assert_eq!(metrics.queue_depth(), 0);
+
+    server.shutdown();
+    server.join().unwrap();
+    assert_eq!(metrics.workers(), 0);
+}
+
+#[test]
+fn panicking_handler_gets_500_and_server_keeps_serving() {
+    let app = App::new();
+    app.workers(1);
+    app.get("/panic", |_, _| panic!("handler failed"));
+    app.get("/", |_, res| res.send("ok"));
+    let server = app.spawn("127.0.0.1:0").unwrap();
+    let addr = server.local_addr();
+
+    for _ in 0..2 {
+        let res = read_all(connect(addr, "GET /panic HTTP/1.1\r\n\r\n"));
+        assert!(res.starts_with("HTTP/1.1 500 "), "{}", res);
+        assert!(res.contains("Connection: close\r\n"));
+    }
+    let res = read_all(connect(addr, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n"));
+    assert!(res.ends_with("ok"));
+
+    server.shutdown();
+    server.join().unwrap();
+}
+
+#[test]
+fn shutdown_closes_idle_connections() {
+    let app = App::new();
+    app.get("/", |_, res| res.send("ok"));
+    let server = app.spawn("127.0.0.1:0").unwrap();
+    let mut idle = connect(server.local_addr(), "GET / HTTP/1.1\r\n\r\n");
+    let mut buf = Vec::new();
+    while !buf.ends_with(b"ok") {
+        let mut chunk = [0; 1024];
+        let n = idle.read(&mut chunk).unwrap();
+        assert!(n > 0);
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    // The connection waits for its next request, shutdown must not.
+    let started = Instant::now();
+    server.shutdown();
+    server.join().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(idle.read(&mut [0; 16]).unwrap(), 0);
+}
+
+#[test]
+fn shutdown_answers_queued_connections_with_503() {
+    let app = App::new();
+    app.workers(1);
+    app.queue_size(1);
+    let (release, blocked) = mpsc::channel::<()>();
+    let blocked = Mutex::new(blocked);
+    app.get("/slow", move |_, res| {
+        let _ = blocked.lock().unwrap().recv();
+        res.send("done")
+    });
+    let metrics = app.metrics();
+    let server = app.spawn("127.0.0.1:0").unwrap();
+    let addr = server.local_addr();
+    let request = "GET /slow HTTP/1.1\r\n\r\n";
+
+    let first = connect(addr, request);
+    wait_for("a busy worker", || metrics.busy_workers() == 1);
+    let queued = connect(addr, request);
+    wait_for("a queued connection", || metrics.queue_depth() == 1);
+    server.shutdown();
+    release.send(()).unwrap();
+    assert!(read_all(first).ends_with("done"));
+    assert!(read_all(queued).starts_with("HTTP/1.1 503 "));
+    server.join().unwrap();
+}
+
+// Hands out the connections sent to it.
+struct Channel {
+    incoming: Mutex<mpsc::Receiver<Box<dyn Connection>>>,
+    sender: mpsc::Sender<Box<dyn Connection>>,
+}
+
+impl Listener for Channel {
+    fn accept(&self) -> io::Result<Box<dyn Connection>> {
+        self.incoming
+            .lock()
+            .unwrap()
+            .recv()
+            .map_err(io::Error::other)
+    }
+    fn waker(&self) -> io::Result<Waker> {
+        let sender = self.sender.clone();
+        Ok(Box::new(move || {
+            let _ = sender.send(Box::new(Duplex::new(io::empty(), io::sink())));
+        }))
+    }
+}
+
+// Forwards everything written, the channel closes with the connection.
+struct Output(mpsc::Sender<Vec<u8>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = self.0.send(buf.to_vec());
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn serves_a_duplex_from_a_custom_listener() {
+    let app = App::new();
+    app.post("/echo", |req, res| res.send(req.body_string()?));
+    let (sender, incoming) = mpsc::channel();
+    let server = app
+        .spawn_on(Channel {
+            incoming: Mutex::new(incoming),
+            sender: sender.clone(),
+        })
+        .unwrap();
+    assert!(server.try_local_addr().is_none());
+
+    let (output, written) = mpsc::channel();
+    let request = "POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello\
+                   GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n";
+    sender
+        .send(Box::new(Duplex::new(
+            Cursor::new(request.as_bytes().to_vec()),
+            Output(output),
+        )))
+        .unwrap();
+    let res = String::from_utf8(written.iter().flatten().collect()).unwrap();
+    assert!(res.starts_with("HTTP/1.1 200 "), "{}", res);
+    assert!(res.contains("\r\n\r\nhelloHTTP/1.1 404 "));
+
+    server.shutdown();
+    server.join().unwrap();
+}
@@ -0,0 +1,183 @@
+#![cfg(feature = "tls")]
+
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme, StreamOwned,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{self, ring},
+    pki_types::{CertificateDer, ServerName, UnixTime, pem::PemObject},
+};
+use web_server::{App, QueuePolicy, TlsConfig};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn cert(name: &str) -> CertificateDer<'static> {
+    CertificateDer::from_pem_file(fixture(&format!("{}.cert.pem", name))).unwrap()
+}
+
+// Trusts any certificate, keeping the one the server presented. The
+// handshake signatures are still checked.
+#[derive(Debug, Default)]
+struct Presented(Mutex<Option<CertificateDer<'static>>>);
+
+impl ServerCertVerifier for Presented {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _: &[CertificateDer<'_>],
+        _: &ServerName<'_>,
+        _: &[u8],
+        _: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        *self.0.lock().unwrap() = Some(end_entity.clone().into_owned());
+        Ok(ServerCertVerified::assertion())
+    }
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        let algorithms = ring::default_provider().signature_verification_algorithms;
+        crypto::verify_tls12_signature(message, cert, dss, &algorithms)
+    }
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        let algorithms = ring::default_provider().signature_verification_algorithms;
+        crypto::verify_tls13_signature(message, cert, dss, &algorithms)
+    }
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+// Sends one request with `server_name` as SNI, returns the response and
+// the certificate the server answered with.
+fn get(addr: SocketAddr, server_name: &str) -> (String, CertificateDer<'static>) {
+    let verifier = Arc::new(Presented::default());
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .dangerous()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+    let name = ServerName::try_from(server_name.to_owned()).unwrap();
+    let conn = ClientConnection::new(Arc::new(config), name).unwrap();
+    let tcp = TcpStream::connect(addr).unwrap();
+    tcp.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut stream = StreamOwned::new(conn, tcp);
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut res = String::new();
+    stream.read_to_string(&mut res).unwrap();
+    let presented = verifier.0.lock().unwrap().take().unwrap();
+    (res, presented)
+}
+
+fn tls_config() -> TlsConfig {
+    TlsConfig::new(fixture("localhost.cert.pem"), fixture("localhost.key.pem")).unwrap()
+}
+
+#[test]
+fn handshake_picks_certificate_by_sni() {
+    let app = App::new();
+    app.get("/", |_, res| res.send("secure"));
+    let tls = tls_config()
+        .add_host(
+            "Example.Test",
+            fixture("example.test.cert.pem"),
+            fixture("example.test.key.pem"),
+        )
+        .unwrap();
+    let server = app.spawn_tls("127.0.0.1:0", tls).unwrap();
+    let addr = server.local_addr();
+
+    let (res, presented) = get(addr, "example.test");
+    assert!(res.starts_with("HTTP/1.1 200 "), "{}", res);
+    assert!(res.ends_with("secure"));
+    assert_eq!(presented, cert("example.test"));
+
+    // Unknown names get the default certificate.
+    let (res, presented) = get(addr, "other.test");
+    assert!(res.ends_with("secure"));
+    assert_eq!(presented, cert("localhost"));
+
+    server.shutdown();
+    server.join().unwrap();
+}
+
+#[test]
+fn rejected_connection_stalling_its_handshake_is_cut_off() {
+    let app = App::new();
+    app.workers(1);
+    app.queue_size(1);
+    app.queue_policy(QueuePolicy::Reject);
+    let (release, blocked) = mpsc::channel::<()>();
+    let blocked = Mutex::new(blocked);
+    app.get("/", move |_, res| {
+        let _ = blocked.lock().unwrap().recv();
+        res.send("done")
+    });
+    let metrics = app.metrics();
+    let server = app.spawn_tls("127.0.0.1:0", tls_config()).unwrap();
+    let addr = server.local_addr();
+    let busy = thread::spawn(move || get(addr, "localhost"));
+    let wait_for = |f: &dyn Fn() -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !f() {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(5));
+        }
+    };
+    wait_for(&|| metrics.busy_workers() == 1);
+    let _queued = TcpStream::connect(addr).unwrap();
+    wait_for(&|| metrics.queue_depth() == 1);
+
+    // The start of a TLS record announcing 512 bytes, sent one byte at a
+    // time so that no read ever times out.
+    let mut stalled = TcpStream::connect(addr).unwrap();
+    stalled
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    let started = Instant::now();
+    let mut record = [0x16, 0x03, 0x01, 0x02, 0x00].into_iter().chain([0; 512]);
+    loop {
+        assert!(started.elapsed() < Duration::from_secs(5), "never cut off");
+        let cut = match stalled.read(&mut [0; 16]) {
+            Ok(0) => true,
+            Err(err) => !matches!(
+                err.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ),
+            Ok(_) => panic!("answered before the handshake"),
+        };
+        if cut || stalled.write_all(&[record.next().unwrap()]).is_err() {
+            break;
+        }
+    }
+    assert!(started.elapsed() >= Duration::from_millis(900));
+
+    release.send(()).unwrap();
+    assert!(busy.join().unwrap().0.ends_with("done"));
+    server.shutdown();
+    server.join().unwrap();
+}